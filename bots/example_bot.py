@@ -0,0 +1,58 @@
+#!/usr/bin/env python3
+"""A small external bot, to start from when writing your own.
+
+Seat it from the server console with `spawn main python3 bots/example_bot.py`. The server sends
+every packet as a line of JSON on stdin, and reads moves as lines of JSON from stdout. Anything
+printed to stderr shows on the server's terminal.
+
+It plays the first card that matches the top card, and picks up when none does or when the
+server turns its move down.
+"""
+
+import json
+import sys
+from collections import Counter
+
+
+def send(packet):
+    print(json.dumps(packet), flush=True)
+
+
+def matches(card, top):
+    if card["color"] is None:
+        return True
+    if card["color"] == top["color"]:
+        return True
+    if card["kind"] == "Number":
+        return card["number"] == top["number"]
+    return card["kind"] == top["kind"]
+
+
+def choose_move(snapshot):
+    table = snapshot["table"]
+    if table["card_debt"] > 0 or table["draw_until_color"] is not None:
+        return "SendMovePick"
+    hand = snapshot["hand"]
+    for card in hand:
+        if not matches(card, table["top_card"]):
+            continue
+        color_choice = None
+        if card["color"] is None:
+            # The color held the most
+            held = Counter(other["color"] for other in hand if other["color"] in snapshot["wild_colors"])
+            color_choice = held.most_common(1)[0][0] if held else snapshot["wild_colors"][0]
+        return {"SendMoveCard": {"card_id": card["id"], "color_choice": color_choice, "target_player": None}}
+    return "SendMovePick"
+
+
+for line in sys.stdin:
+    packet = json.loads(line)
+    if not isinstance(packet, dict):
+        continue
+    if "SendMoveAcknowledgement" in packet and packet["SendMoveAcknowledgement"]["error"] is not None:
+        print(f"Move turned down: {packet['SendMoveAcknowledgement']['error']}", file=sys.stderr)
+        send("SendMovePick")
+    elif "SendGameState" in packet:
+        snapshot = packet["SendGameState"]["snapshot"]
+        if snapshot["you"] is not None and snapshot["turn"] == snapshot["you"]:
+            send(choose_move(snapshot))
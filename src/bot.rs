@@ -0,0 +1,211 @@
+/* Bots
+ * - Pick a move for the player whose turn it is. Moves are only ever taken from the ones the
+ * rule set accepts (RuleSet::verify_move), so a bot never sends a move that gets turned down.
+ * - Nothing network related: the server turns a Move into the same thing a client would send.
+ */
+
+use std::time::Duration;
+
+use rand::{thread_rng, Rng, seq::SliceRandom};
+use serde::{Serialize, Deserialize};
+
+use crate::card::{CardId, CardKind, Color};
+use crate::game::GameState;
+use crate::rules::RuleSet;
+use crate::search;
+
+/// How well the built-in bots play
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum Difficulty {
+    /// Plays any card it can, and sometimes picks up for no reason
+    Easy,
+    /// Gets rid of its most valuable cards first (see greedy_move)
+    Normal,
+    /// Keeps its wilds and draw cards for when someone is about to win
+    Hard,
+    /// Plays games out to the end to see which move works best (see search.rs)
+    Expert,
+}
+
+impl Difficulty {
+    /// `budget` is how long an expert bot may think
+    pub fn choose_move(self, rules: &dyn RuleSet, state: &GameState, budget: Duration) -> Move {
+        match self {
+            Difficulty::Easy => random_move(rules, state),
+            Difficulty::Normal => greedy_move(rules, state),
+            Difficulty::Hard => careful_move(rules, state),
+            Difficulty::Expert => search::search_move(rules, state, budget),
+        }
+    }
+
+    /// How long the bot waits before moving, so that the table has time to follow
+    pub fn think_time(self) -> Duration {
+        Duration::from_millis(thread_rng().gen_range(800..2500))
+    }
+}
+
+/// A move of the player whose turn it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Move {
+    /// `target_player` is 0 based, unlike in ClientPacket::SendMoveCard
+    Card {card_id: CardId, color_choice: Option<Color>, target_player: Option<usize>},
+    Pick,
+}
+
+/// Every move the current player can make. Wilds come once per color they can be given, and
+/// cards played on someone once per other player still in the game. Picking up is always allowed.
+pub fn legal_moves(rules: &dyn RuleSet, state: &GameState) -> Vec<Move> {
+    let mut moves = vec![];
+    for card in state.current_hand().iter() {
+        let colors = match card.kind.is_wild() {
+            true => rules.wild_colors(state).into_iter().map(Some).collect(),
+            false => vec![None],
+        };
+        let targets = match rules.takes_target(card) {
+            true => (0..state.players.len()).filter(|&player| player != state.turn && state.players[player].is_active).map(Some).collect(),
+            false => vec![None],
+        };
+        for &color_choice in &colors {
+            let mut card = card.clone();
+            if let Some(color) = color_choice {
+                card.set_draw4_or_wild_color(color);
+            }
+            if rules.verify_wild_color(state, &card).is_err() {
+                continue;
+            }
+            for &target_player in &targets {
+                if rules.verify_move(state, &card, target_player).is_ok() {
+                    moves.push(Move::Card { card_id: card.id, color_choice, target_player });
+                }
+            }
+        }
+    }
+    moves.push(Move::Pick);
+    moves
+}
+
+/// The color the current player holds the most of, among those a wild can be given
+pub fn best_wild_color(rules: &dyn RuleSet, state: &GameState) -> Option<Color> {
+    let hand = state.current_hand();
+    rules.wild_colors(state).into_iter().max_by_key(|&color| hand.iter().filter(|card| card.color == Some(color)).count())
+}
+
+/// Gets rid of the card worth the most points, keeping wilds for when nothing else can be
+/// played. Wilds are given the color held the most, and cards played on someone go to whoever
+/// has the fewest cards.
+pub fn greedy_move(rules: &dyn RuleSet, state: &GameState) -> Move {
+    let hand = state.current_hand();
+    let wild_color = best_wild_color(rules, state);
+    let score = |candidate: &Move| match *candidate {
+        Move::Pick => (0, 0, 0),
+        Move::Card { card_id, color_choice, target_player } => {
+            let card = hand.get(card_id).unwrap();
+            let not_wild = if card.kind.is_wild() {0} else {1};
+            let right_color = if color_choice.is_none() || color_choice == wild_color {1} else {0};
+            let fewest_cards = target_player.map_or(0, |target| usize::MAX - state.players[target].hand.len());
+            (1 + not_wild * 2 + right_color, rules.card_points(card), fewest_cards)
+        }
+    };
+    legal_moves(rules, state).into_iter().max_by_key(score).unwrap_or(Move::Pick)
+}
+
+/// Plays a card at random, wilds included, and picks up once in a while even when it could play
+pub fn random_move(rules: &dyn RuleSet, state: &GameState) -> Move {
+    let moves = legal_moves(rules, state);
+    let mut rng = thread_rng();
+    if rng.gen_bool(0.2) {
+        return Move::Pick;
+    }
+    let cards = &moves[..moves.len() - 1];
+    cards.choose(&mut rng).copied().unwrap_or(Move::Pick)
+}
+
+/// Like greedy_move, but holds on to wilds and cards that hurt the next player for as long as
+/// nobody is close to winning. Once the next player is down to 2 cards, those are played first.
+/// Otherwise it follows the color it holds the most of, so that it can keep playing.
+pub fn careful_move(rules: &dyn RuleSet, state: &GameState) -> Move {
+    let hand = state.current_hand();
+    let wild_color = best_wild_color(rules, state);
+    let mut after = state.clone();
+    rules.next_player(&mut after);
+    let threatened = after.turn != state.turn && after.players[after.turn].hand.len() <= 2;
+    let score = |candidate: &Move| match *candidate {
+        Move::Pick => (0, 0, 0, 0),
+        Move::Card { card_id, color_choice, target_player } => {
+            let card = hand.get(card_id).unwrap();
+            let attack = card.kind.draw_value() > 0 || matches!(card.kind, CardKind::Skip | CardKind::Reverse | CardKind::SkipEveryone);
+            let kept = !card.kind.is_wild() && !attack;
+            let urgency = if threatened {attack as usize} else {kept as usize};
+            let right_color = color_choice.is_none() || color_choice == wild_color;
+            let color = color_choice.or(card.color);
+            let same_color = hand.iter().filter(|other| other.id != card_id && other.color.is_some() && other.color == color).count();
+            let fewest_cards = target_player.map_or(0, |target| usize::MAX - state.players[target].hand.len());
+            (1 + urgency * 2 + right_color as usize, same_color, rules.card_points(card), fewest_cards)
+        }
+    };
+    legal_moves(rules, state).into_iter().max_by_key(score).unwrap_or(Move::Pick)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::{Card, CardKind, Hand};
+    use crate::deck_definition::DeckDefinition;
+    use crate::rules::RulesKind;
+
+    fn card(id: CardId, kind: CardKind, color: Option<Color>, number: Option<u8>) -> Card {
+        Card { id, kind, color, number, other_side: None }
+    }
+
+    #[test]
+    fn greedy_moves() {
+        let rules = RulesKind::Official.rule_set(DeckDefinition::standard());
+        let mut state = GameState::new(rules.as_ref(), 2, 1);
+        state.stack.push_front(card(1000, CardKind::Number, Some(Color::Red), Some(5)));
+        state.card_debt = 0;
+        state.draw_until_color = None;
+        let hand = &mut state.players[state.turn].hand;
+        *hand = Hand::empty();
+        hand.push(card(1, CardKind::Number, Some(Color::Red), Some(2)));
+        hand.push(card(2, CardKind::Number, Some(Color::Blue), Some(9)));
+        hand.push(card(3, CardKind::Number, Some(Color::Blue), Some(5)));
+        hand.push(card(4, CardKind::Wild, None, None));
+        // The blue 9 can't be played, the blue 5 is worth more than the red 2
+        let moves = legal_moves(rules.as_ref(), &state);
+        assert!(!moves.contains(&Move::Card { card_id: 2, color_choice: None, target_player: None }));
+        assert_eq!(moves.last(), Some(&Move::Pick));
+        assert_eq!(greedy_move(rules.as_ref(), &state), Move::Card { card_id: 3, color_choice: None, target_player: None });
+        // With only the wild left to play, it takes the color held the most
+        state.players[state.turn].hand.remove(1);
+        state.players[state.turn].hand.remove(3);
+        assert_eq!(greedy_move(rules.as_ref(), &state), Move::Card { card_id: 4, color_choice: Some(Color::Blue), target_player: None });
+    }
+
+    #[test]
+    fn careful_moves() {
+        let rules = RulesKind::Official.rule_set(DeckDefinition::standard());
+        let mut state = GameState::new(rules.as_ref(), 2, 1);
+        state.stack.push_front(card(1000, CardKind::Number, Some(Color::Red), Some(5)));
+        state.card_debt = 0;
+        state.skip_debt = 0;
+        state.draw_until_color = None;
+        state.direction = crate::game::Direction::Positive;
+        let hand = &mut state.players[state.turn].hand;
+        *hand = Hand::empty();
+        hand.push(card(1, CardKind::Draw2, Some(Color::Red), None));
+        hand.push(card(2, CardKind::Number, Some(Color::Red), Some(3)));
+        hand.push(card(3, CardKind::Number, Some(Color::Blue), Some(5)));
+        hand.push(card(4, CardKind::Number, Some(Color::Blue), Some(7)));
+        // The Draw2 is kept, and the blue 5 is played since more blue cards follow it
+        let next = 1 - state.turn;
+        assert_eq!(careful_move(rules.as_ref(), &state), Move::Card { card_id: 3, color_choice: None, target_player: None });
+        // The next player is about to win
+        state.players[next].hand = Hand::empty();
+        state.players[next].hand.push(card(5, CardKind::Number, Some(Color::Green), Some(1)));
+        assert_eq!(careful_move(rules.as_ref(), &state), Move::Card { card_id: 1, color_choice: None, target_player: None });
+        // Easy bots only ever play what can be played
+        for _ in 0..20 {
+            assert!(legal_moves(rules.as_ref(), &state).contains(&random_move(rules.as_ref(), &state)));
+        }
+    }
+}
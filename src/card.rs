@@ -5,51 +5,168 @@ use strum_macros::{EnumIter, Display};
 use CardKind::*;
 use colored::*;
 
-use rand::{thread_rng, Rng};
+use rand::{thread_rng, Rng, seq::SliceRandom};
 
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Display, Serialize, Deserialize, Clone, PartialEq)]
-pub enum CardKind {Number, Skip, Reverse, Draw2, Draw4, Wild}
+use crate::deck_definition::DeckDefinition;
+use crate::theme::{Palette, Theme};
 
-#[derive(Debug, Display, EnumIter, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
-pub enum Color {Red, Green, Blue, Yellow}
+#[derive(Debug, Display, Serialize, Deserialize, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CardKind {
+    Number, Skip, Reverse, Draw2, Draw4, Wild,
+    // Uno Flip, light side
+    Draw1, Flip,
+    #[strum(serialize = "Wild Draw2")]
+    WildDraw2,
+    // Uno Flip, dark side
+    Draw5,
+    #[strum(serialize = "Skip Everyone")]
+    SkipEveryone,
+    #[strum(serialize = "Wild Draw Color")]
+    WildDrawColor,
+    // Uno No Mercy
+    #[strum(serialize = "Discard All")]
+    DiscardAll,
+    /// A Draw4 that has a color of its own, rather than being wild
+    #[strum(serialize = "Draw4")]
+    ColoredDraw4,
+    #[strum(serialize = "Wild Draw6")]
+    WildDraw6,
+    #[strum(serialize = "Wild Draw10")]
+    WildDraw10,
+    #[strum(serialize = "Wild Reverse Draw4")]
+    WildReverseDraw4,
+    #[strum(serialize = "Wild Color Roulette")]
+    WildColorRoulette,
+}
+
+impl CardKind {
+    /// Power cards that come in every color
+    pub fn is_colored_action(&self) -> bool {
+        matches!(self, Skip | Reverse | Draw2 | Draw1 | Flip | Draw5 | SkipEveryone | DiscardAll | ColoredDraw4)
+    }
+
+    /// Cards that get their color from the player who plays them
+    pub fn is_wild(&self) -> bool {
+        matches!(self, Wild | Draw4 | WildDraw2 | WildDrawColor | WildDraw6 | WildDraw10 | WildReverseDraw4 | WildColorRoulette)
+    }
+
+    /// How many cards the next player has to draw because of this card
+    pub fn draw_value(&self) -> usize {
+        match self {
+            Draw1 => 1,
+            Draw2 | WildDraw2 => 2,
+            Draw4 | ColoredDraw4 | WildReverseDraw4 => 4,
+            Draw5 => 5,
+            WildDraw6 => 6,
+            WildDraw10 => 10,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Display, EnumIter, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Color {
+    Red, Green, Blue, Yellow,
+    // Dark side of Uno Flip
+    Pink, Teal, Orange, Purple,
+}
+
+impl Color {
+    /// Colors of a normal deck, and of the light side in Uno Flip
+    pub const LIGHT: [Color; 4] = [Color::Red, Color::Green, Color::Blue, Color::Yellow];
+    /// Colors of the dark side in Uno Flip
+    pub const DARK: [Color; 4] = [Color::Pink, Color::Teal, Color::Orange, Color::Purple];
+
+    /// Follows the installed theme. The light colors of the standard palette are left to the
+    /// terminal so that they look right with any color scheme.
+    pub fn terminal_color(&self) -> colored::Color {
+        let theme = Theme::current();
+        match (theme.palette, self) {
+            (Palette::Standard, Color::Red) => colored::Color::Red,
+            (Palette::Standard, Color::Green) => colored::Color::Green,
+            (Palette::Standard, Color::Blue) => colored::Color::Blue,
+            (Palette::Standard, Color::Yellow) => colored::Color::Yellow,
+            _ => {
+                let (r, g, b) = theme.rgb(Some(*self));
+                colored::Color::TrueColor { r, g, b }
+            }
+        }
+    }
+
+    /// Parses what a player typed as a color. Any unambiguous prefix works (eg: `b`, `blu`,
+    /// `pi` for pink, `pu` for purple).
+    pub fn from_input(input: &str) -> Option<Color> {
+        let input = input.trim().to_lowercase();
+        if input.is_empty() {
+            return None;
+        }
+        let mut matching = Color::iter().filter(|color| color.to_string().to_lowercase().starts_with(&input));
+        match (matching.next(), matching.next()) {
+            (Some(color), None) => Some(color),
+            _ => None,
+        }
+    }
+}
 
+/// Terminal color used for cards that have no color yet (wilds in the hand)
+const NO_COLOR_TERMINAL_COLOR : colored::Color = colored::Color::Cyan;
+
+
+/// Identifies a card within a game, see `Card::id`
+pub type CardId = u32;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Card {
+    /// Unique among the cards of a deck, and stays the same as the card moves between the deck,
+    /// hands and the stack. Both sides of a Flip card share it.
+    pub id: CardId,
     pub kind: CardKind,
     pub color: Option<Color>,
     pub number: Option<u8>,
+    /// The dark side of the card in Uno Flip, None in every other mode. `kind`, `color` and
+    /// `number` always describe the side that is currently in play.
+    pub other_side: Option<Box<Card>>,
 }
 
 impl Card {
     fn new_number(number : u8, color: Color) -> Card {
-        Card {kind: CardKind::Number, color:Some(color), number:Some(number)}
+        Card {id: 0, kind: CardKind::Number, color:Some(color), number:Some(number), other_side: None}
     }
     fn new_power(kind : CardKind, color: Option<Color>) -> Card {
         match kind {
-            Skip | Reverse | Draw2 => Card {kind, color, number:None},
-            Draw4 | Wild => Card {kind, color:None, number:None},
-            Number => panic!("Invalid kind for power card")
+            Number => panic!("Invalid kind for power card"),
+            _ if kind.is_wild() => Card {id: 0, kind, color:None, number:None, other_side: None},
+            _ => Card {id: 0, kind, color, number:None, other_side: None},
         }
     }
     pub fn set_draw4_or_wild_color(&mut self, color: Color) {
-        match self.kind {
-            Draw4 | Wild => self.color = Some(color),
-            _ => panic!("This should not happen!!"),
+        match self.kind.is_wild() {
+            true => self.color = Some(color),
+            false => panic!("This should not happen!!"),
+        }
+    }
+
+    /// Wilds and Draw4s get a color when played; it has to be cleared before they go back into the deck
+    pub fn reset_wild_color(&mut self) {
+        if self.kind.is_wild() {
+            self.color = None;
+        }
+    }
+
+    /// Turns the card over (Uno Flip). Cards without another side are left as they are.
+    pub fn flip(&mut self) {
+        if let Some(mut other_side) = self.other_side.take() {
+            self.reset_wild_color();
+            std::mem::swap(self, &mut *other_side);
+            self.other_side = Some(other_side);
         }
     }
 
     pub fn get_colorized_repr(&self) -> String {
-        let color_str = match self.color {
-            Some(Color::Red) => "red",
-            Some(Color::Green) => "green",
-            Some(Color::Blue) => "blue",
-            Some(Color::Yellow) => "yellow",
-            None => "cyan"
-        };
-        self.to_string().color(color_str).to_string()
+        let color = self.color.map_or(NO_COLOR_TERMINAL_COLOR, |color| color.terminal_color());
+        self.to_string().color(color).to_string()
     }
 }
 
@@ -71,48 +188,135 @@ impl fmt::Display for Card {
     }
 }
 
-#[derive(Debug)]
+/// How many players one standard deck comfortably serves before another deck gets mixed in
+pub const PLAYERS_PER_DECK : usize = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Deck(Vec<Card>);
 impl Deck {
-    pub fn new() -> Deck {
-        let mut deck_vec : Vec<Card> = Vec::with_capacity(112);
+    pub fn from_definition(definition: &DeckDefinition) -> Deck {
+        let mut deck_vec : Vec<Card> = Vec::with_capacity(definition.total_cards());
 
-        for color in Color::iter() {
-            deck_vec.push(Card::new_number(0, color)); // 0 card
+        for &color in &definition.colors {
+            for (num, &copies) in definition.numbers_for(color).iter().enumerate() {
+                (0..copies).for_each(|_| deck_vec.push(Card::new_number(num as u8, color)));
+            }
+            for (kind, &copies) in &definition.actions_for(color) {
+                (0..copies).for_each(|_| deck_vec.push(Card::new_power(kind.clone(), Some(color))));
+            }
+        }
 
-            // 2 sets of: 1-9, draw2, skip, reverse
-            for _ in 0..2 {
-                for num in 1..=9 {
-                    deck_vec.push(Card::new_number(num, color));
-                }
+        for (kind, &copies) in &definition.wilds {
+            (0..copies).for_each(|_| deck_vec.push(Card::new_power(kind.clone(), None)));
+        }
 
-                deck_vec.push(Card::new_power(Draw2, Some(color)));
-                deck_vec.push(Card::new_power(Reverse, Some(color)));
-                deck_vec.push(Card::new_power(Skip, Some(color)));
+        Deck::numbered(deck_vec)
+    }
+
+    /// Gives every card its id
+    fn numbered(mut cards: Vec<Card>) -> Deck {
+        for (id, card) in cards.iter_mut().enumerate() {
+            card.id = id as CardId;
+            if let Some(other_side) = card.other_side.as_mut() {
+                other_side.id = id as CardId;
             }
+        }
+        Deck(cards)
+    }
 
+    /// Combines `num_decks` decks of the given definition into one big deck, for large tables
+    pub fn with_decks(definition: &DeckDefinition, num_decks: usize) -> Deck {
+        let mut deck_vec : Vec<Card> = Vec::with_capacity(definition.total_cards() * num_decks);
+        for _ in 0..num_decks {
+            deck_vec.append(&mut Deck::from_definition(definition).0);
         }
+        Deck::numbered(deck_vec)
+    }
 
-        // 4 wilds and 4 draw4s
-        for _ in 0..4 {
-            deck_vec.push(Card::new_power(Wild, None));
-            deck_vec.push(Card::new_power(Draw4, None));
+    /// Builds an Uno Flip deck: every light side card gets a random dark side card glued to its
+    /// back. Both definitions must have the same number of cards.
+    pub fn with_flip_decks(light: &DeckDefinition, dark: &DeckDefinition, num_decks: usize) -> Deck {
+        assert_eq!(light.total_cards(), dark.total_cards(), "Both sides of a Flip deck need the same number of cards");
+        let Deck(mut light_cards) = Deck::with_decks(light, num_decks);
+        let Deck(mut dark_cards) = Deck::with_decks(dark, num_decks);
+        dark_cards.shuffle(&mut thread_rng());
+        for (light_card, dark_card) in light_cards.iter_mut().zip(dark_cards) {
+            light_card.other_side = Some(Box::new(dark_card));
         }
+        Deck::numbered(light_cards)
+    }
+
+    /// Turns every card of the deck over (Uno Flip)
+    pub fn flip(&mut self) {
+        self.0.iter_mut().for_each(Card::flip);
+    }
+
+    /// Number of standard decks needed so that `num_players` don't run out of cards
+    pub fn decks_for_players(num_players: usize) -> usize {
+        num_players.div_ceil(PLAYERS_PER_DECK).max(1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
 
-        Deck(deck_vec)
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
     }
 
+    /// Panics if the deck is empty, use `try_pop_random_card` when that can happen
     pub fn pop_random_card(&mut self) -> Card {
-        // Card::new_number(1, Color::Green)
-        self.0.remove(thread_rng().gen_range(0..self.0.len()))
+        self.try_pop_random_card().expect("Tried to take a card from an empty deck")
     }
 
-    pub fn push_card(&mut self, card: Card) {
-        self.0.push(card);
+    /// Takes a random card for which `predicate` holds, if there is one
+    pub fn pop_random_card_where<P: Fn(&Card) -> bool>(&mut self, predicate: P) -> Option<Card> {
+        let candidates = (0..self.0.len()).filter(|&idx| predicate(&self.0[idx])).collect::<Vec<usize>>();
+        let idx = *candidates.choose(&mut thread_rng())?;
+        Some(self.0.remove(idx))
+    }
+
+    pub fn try_pop_random_card(&mut self) -> Option<Card> {
+        if self.0.is_empty() {
+            return None;
+        }
+        Some(self.0.remove(thread_rng().gen_range(0..self.0.len())))
+    }
+
+    /// Puts the given (discarded) cards back into the deck and shuffles it
+    pub fn refill<I: IntoIterator<Item = Card>>(&mut self, cards: I) {
+        for mut card in cards {
+            card.reset_wild_color();
+            self.0.push(card);
+        }
+        self.0.shuffle(&mut thread_rng());
     }
 
 }
 
+/// Orders in which a client can show its hand. Moves refer to card ids, so the order is only
+/// cosmetic and never has to match the server's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum HandSort {
+    /// The order the cards were picked up in
+    Dealt,
+    Color,
+    Kind,
+    Number,
+}
+
+impl HandSort {
+    /// The sort the toggle switches to next
+    pub fn next(self) -> HandSort {
+        match self {
+            HandSort::Dealt => HandSort::Color,
+            HandSort::Color => HandSort::Kind,
+            HandSort::Kind => HandSort::Number,
+            HandSort::Number => HandSort::Dealt,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Hand(Vec<Card>);
 impl Hand {
@@ -124,43 +328,90 @@ impl Hand {
         Hand(cards)
     }
 
+    pub fn empty() -> Hand {
+        Hand(vec![])
+    }
+
     pub fn len(&self) -> usize {
         self.0.len()
     }
 
-    //TODO: validate bounds
-    pub fn pop_at(&mut self, index : usize) -> Card {
-        self.0.remove(index - 1)
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
     }
 
+    /// The card shown at `index` (1 based) when the hand is printed
     pub fn get_at(&self, index: usize) -> Card {
         self.0[index - 1].clone()
     }
 
+    pub fn get(&self, id: CardId) -> Option<&Card> {
+        self.0.iter().find(|card| card.id == id)
+    }
+
+    pub fn remove(&mut self, id: CardId) -> Option<Card> {
+        let idx = self.0.iter().position(|card| card.id == id)?;
+        Some(self.0.remove(idx))
+    }
+
     pub fn push(&mut self, card: Card) {
         self.0.push(card);
     }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Card> {
+        self.0.iter()
+    }
+
+    /// Takes every card for which `predicate` holds out of the hand
+    pub fn remove_where<P: Fn(&Card) -> bool>(&mut self, predicate: P) -> Vec<Card> {
+        let (removed, kept) = self.0.drain(..).partition(predicate);
+        self.0 = kept;
+        removed
+    }
+
+    /// Empties the hand, eg: when its owner is out of the game
+    pub fn take_all(&mut self) -> Vec<Card> {
+        std::mem::take(&mut self.0)
+    }
+
+    /// Turns every card of the hand over (Uno Flip)
+    pub fn flip(&mut self) {
+        self.0.iter_mut().for_each(Card::flip);
+    }
+
+    /// Wilds go after the colored cards, and cards without a number after the numbered ones.
+    /// Ties are broken by id, which keeps the dealt order stable.
+    pub fn sort(&mut self, by: HandSort) {
+        match by {
+            HandSort::Dealt => self.0.sort_by_key(|card| card.id),
+            HandSort::Color => self.0.sort_by_key(|card| (card.color.is_none(), card.color, card.kind.clone(), card.number, card.id)),
+            HandSort::Kind => self.0.sort_by_key(|card| (card.kind.clone(), card.color.is_none(), card.color, card.number, card.id)),
+            HandSort::Number => self.0.sort_by_key(|card| (card.number.is_none(), card.number, card.kind.clone(), card.color.is_none(), card.color, card.id)),
+        }
+    }
 }
 
-impl fmt::Display for Hand {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "Your hand is:").unwrap();
-        for i in 0..self.0.len() {
-            let color_str = match self.0[i].color {
-                Some(color) => {
-                    match color {
-                        Color::Red => "red",
-                        Color::Green => "green",
-                        Color::Blue => "blue",
-                        Color::Yellow => "yellow"
-                    }
-                }
-                None => "cyan",
+impl Hand {
+    /// Like Display, but cards for which `is_marked` holds are starred and printed in bold (eg:
+    /// the cards that can be played right now)
+    pub fn to_string_marking<P: Fn(&Card) -> bool>(&self, is_marked: P) -> String {
+        let mut ret_string = "Your hand is:\n".to_string();
+        for (i, card) in self.0.iter().enumerate() {
+            let color = card.color.map_or(NO_COLOR_TERMINAL_COLOR, |color| color.terminal_color());
+            let marker = card.color.and_then(|color| Theme::current().marker(color)).map_or(String::new(), |marker| format!(" ({marker})"));
+            let line = match is_marked(card) {
+                true => format!("[{}] * {}{}\n", i+1, card, marker).color(color).bold(),
+                false => format!("[{}]   {}{}\n", i+1, card, marker).color(color),
             };
-            let uncolored_str = format!("[{}]  {}\n", i+1, self.0[i]);
-            f.write_str(&format!("{}", uncolored_str.color(color_str))).unwrap();
+            ret_string += &line.to_string();
         }
-        Ok(())
+        ret_string
+    }
+}
+
+impl fmt::Display for Hand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_string_marking(|_| false))
     }
 }
 
@@ -170,3 +421,109 @@ impl fmt::Debug for Hand {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standard_deck_size() {
+        assert_eq!(Deck::from_definition(&DeckDefinition::standard()).len(), 108);
+    }
+
+    #[test]
+    fn multiple_decks() {
+        assert_eq!(Deck::with_decks(&DeckDefinition::standard(), 3).len(), 3 * 108);
+        assert_eq!(Deck::decks_for_players(2), 1);
+        assert_eq!(Deck::decks_for_players(PLAYERS_PER_DECK), 1);
+        assert_eq!(Deck::decks_for_players(PLAYERS_PER_DECK + 1), 2);
+    }
+
+    #[test]
+    fn deck_from_definition() {
+        let definition : DeckDefinition = toml::from_str(include_str!("../decks/kids.toml")).unwrap();
+        let mut deck = Deck::from_definition(&definition);
+        assert_eq!(deck.len(), 80);
+        assert!(deck.pop_random_card_where(|card| card.kind == Skip).is_none());
+        assert_eq!(deck.pop_random_card_where(|card| card.kind == Wild).unwrap().kind, Wild);
+    }
+
+    #[test]
+    fn flip_deck() {
+        let light = DeckDefinition::flip_light();
+        let mut deck = Deck::with_flip_decks(&light, &DeckDefinition::flip_dark(), 1);
+        assert_eq!(deck.len(), 112);
+        let mut card = deck.pop_random_card();
+        assert!(Color::LIGHT.contains(&card.color.unwrap_or(Color::Red)));
+        card.flip();
+        assert!(Color::DARK.contains(&card.color.unwrap_or(Color::Pink)));
+        assert!(card.other_side.is_some());
+        card.flip();
+        assert!(Color::LIGHT.contains(&card.color.unwrap_or(Color::Red)));
+    }
+
+    #[test]
+    fn flipping_clears_wild_color() {
+        let mut wild = Card::new_power(WildDraw2, None);
+        wild.other_side = Some(Box::new(Card::new_number(3, Color::Teal)));
+        wild.set_draw4_or_wild_color(Color::Blue);
+        wild.flip();
+        wild.flip();
+        assert_eq!(wild.kind, WildDraw2);
+        assert_eq!(wild.color, None);
+    }
+
+    #[test]
+    fn remove_from_hand() {
+        let mut hand = Hand(vec![Card::new_number(1, Color::Red), Card::new_number(2, Color::Blue), Card::new_power(Skip, Some(Color::Red))]);
+        let removed = hand.remove_where(|card| card.color == Some(Color::Red));
+        assert_eq!(removed.len(), 2);
+        assert_eq!(hand.len(), 1);
+        assert_eq!(hand.take_all().len(), 1);
+        assert!(hand.is_empty());
+    }
+
+    #[test]
+    fn sorting_hand() {
+        let mut hand = Hand(vec![Card::new_power(Wild, None), Card::new_number(7, Color::Blue), Card::new_power(Skip, Some(Color::Red)), Card::new_number(2, Color::Blue)]);
+        hand.0.iter_mut().enumerate().for_each(|(id, card)| card.id = id as CardId);
+        let ids = |hand: &Hand| hand.iter().map(|card| card.id).collect::<Vec<CardId>>();
+        hand.sort(HandSort::Color);
+        assert_eq!(ids(&hand), vec![2, 3, 1, 0]);
+        hand.sort(HandSort::Number);
+        assert_eq!(ids(&hand), vec![3, 1, 2, 0]);
+        hand.sort(HandSort::Kind);
+        assert_eq!(ids(&hand), vec![3, 1, 2, 0]);
+        hand.sort(HandSort::Dealt);
+        assert_eq!(ids(&hand), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn card_ids_are_unique() {
+        let deck = Deck::with_flip_decks(&DeckDefinition::flip_light(), &DeckDefinition::flip_dark(), 2);
+        let mut ids = deck.0.iter().map(|card| card.id).collect::<Vec<CardId>>();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), deck.len());
+        assert!(deck.0.iter().all(|card| card.other_side.as_ref().unwrap().id == card.id));
+    }
+
+    #[test]
+    fn color_input() {
+        assert_eq!(Color::from_input("b"), Some(Color::Blue));
+        assert_eq!(Color::from_input("Yellow"), Some(Color::Yellow));
+        assert_eq!(Color::from_input("pu"), Some(Color::Purple));
+        assert_eq!(Color::from_input("p"), None);
+        assert_eq!(Color::from_input("x"), None);
+    }
+
+    #[test]
+    fn refill_clears_wild_colors() {
+        let mut deck = Deck(vec![]);
+        let mut wild = Card::new_power(Wild, None);
+        wild.set_draw4_or_wild_color(Color::Red);
+        deck.refill(vec![wild]);
+        assert_eq!(deck.pop_random_card().color, None);
+        assert!(deck.try_pop_random_card().is_none());
+    }
+}
@@ -0,0 +1,180 @@
+/* Card art
+ * - Draws cards as little bordered boxes, side by side, for the line client. Colors are written
+ * as raw escapes so that 256-color terminals get the closest color instead of a truecolor escape
+ * they might not understand.
+ */
+
+use crate::card::{Card, CardKind, Color, Hand};
+use crate::theme::Theme;
+
+/// Width of a card, borders included
+const CARD_WIDTH : usize = 7;
+/// Space between two cards in a row
+const CARD_GAP : usize = 1;
+/// Below this many columns the hand is printed as plain text instead
+pub const MIN_ART_WIDTH : usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorDepth {
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorDepth {
+    /// Terminals that can do truecolor say so in COLORTERM
+    pub fn detect() -> ColorDepth {
+        match std::env::var("COLORTERM").map(|value| value.to_lowercase()) {
+            Ok(value) if value == "truecolor" || value == "24bit" => ColorDepth::TrueColor,
+            _ => ColorDepth::Ansi256,
+        }
+    }
+
+    fn fg(self, theme: &Theme, color: Option<Color>) -> String {
+        match self {
+            ColorDepth::TrueColor => {
+                let (r, g, b) = theme.rgb(color);
+                format!("\x1b[38;2;{};{};{}m", r, g, b)
+            }
+            ColorDepth::Ansi256 => format!("\x1b[38;5;{}m", theme.ansi256(color)),
+        }
+    }
+}
+
+const RESET : &str = "\x1b[0m";
+const BOLD : &str = "\x1b[1m";
+
+/// What is printed in the middle of the card
+pub fn symbol(card: &Card) -> String {
+    match (&card.kind, card.number) {
+        (CardKind::Number, Some(number)) => number.to_string(),
+        (CardKind::Skip, _) => "⊘".to_string(),
+        (CardKind::SkipEveryone, _) => "⊘⊘".to_string(),
+        (CardKind::Reverse, _) => "⇄".to_string(),
+        (CardKind::Flip, _) => "⇅".to_string(),
+        (CardKind::DiscardAll, _) => "ALL".to_string(),
+        (CardKind::Wild, _) => "W".to_string(),
+        (CardKind::WildReverseDraw4, _) => "⇄+4".to_string(),
+        (CardKind::WildDrawColor, _) => "+?".to_string(),
+        (CardKind::WildColorRoulette, _) => "?".to_string(),
+        (kind, _) => format!("+{}", kind.draw_value()),
+    }
+}
+
+/// The corners show a short version of the symbol, like on a real card
+fn corner(card: &Card) -> String {
+    match card.kind {
+        CardKind::WildReverseDraw4 => "+4".to_string(),
+        _ => symbol(card),
+    }
+}
+
+/// `text` centered in `width` columns
+fn center(text: &str, width: usize) -> String {
+    let len = text.chars().count();
+    let left = width.saturating_sub(len) / 2;
+    let right = width.saturating_sub(len + left);
+    format!("{}{}{}", " ".repeat(left), text, " ".repeat(right))
+}
+
+/// The lines of one card, without colors. The color marker of the theme, if any, goes in the
+/// other corners.
+fn card_lines(card: &Card, theme: &Theme) -> [String; 5] {
+    let inner = CARD_WIDTH - 2;
+    let corner = corner(card);
+    let marker = card.color.and_then(|color| theme.marker(color)).unwrap_or("");
+    let pad = inner.saturating_sub(corner.chars().count() + marker.chars().count());
+    [
+        format!("╭{}╮", "─".repeat(inner)),
+        format!("│{}{}{}│", corner, " ".repeat(pad), marker),
+        format!("│{}│", center(&symbol(card), inner)),
+        format!("│{}{}{}│", marker, " ".repeat(pad), corner),
+        format!("╰{}╯", "─".repeat(inner)),
+    ]
+}
+
+/// Draws the hand as rows of cards that fit in `width` columns, numbered like the text version.
+/// Cards for which `is_marked` holds are drawn in bold and get a star next to their number.
+/// Returns None when the terminal is too narrow, in which case the plain text should be used.
+pub fn render_hand<P: Fn(&Card) -> bool>(hand: &Hand, is_marked: P, width: usize, depth: ColorDepth, theme: &Theme) -> Option<String> {
+    if width < MIN_ART_WIDTH {
+        return None;
+    }
+    let per_row = ((width + CARD_GAP) / (CARD_WIDTH + CARD_GAP)).max(1);
+    let cards = hand.iter().collect::<Vec<&Card>>();
+    let mut ret_string = "Your hand is:\n".to_string();
+    for (row_idx, row) in cards.chunks(per_row).enumerate() {
+        let mut lines = vec![String::new(); 6];
+        for (col_idx, card) in row.iter().enumerate() {
+            let marked = is_marked(card);
+            let (style, reset) = match theme.use_color {
+                true => (format!("{}{}", if marked {BOLD} else {""}, depth.fg(theme, card.color)), RESET),
+                false => (if marked {BOLD} else {""}.to_string(), if marked {RESET} else {""}),
+            };
+            let gap = if col_idx == 0 {""} else {" "};
+            for (line, card_line) in lines.iter_mut().zip(card_lines(card, theme)) {
+                *line += &format!("{}{}{}{}", gap, style, card_line, reset);
+            }
+            let label = format!("[{}]{}", row_idx * per_row + col_idx + 1, if marked {"*"} else {""});
+            lines[5] += &format!("{}{}", gap, center(&label, CARD_WIDTH));
+        }
+        for line in lines {
+            ret_string += line.trim_end();
+            ret_string += "\n";
+        }
+    }
+    Some(ret_string)
+}
+
+/// Columns of the terminal the client runs in, 80 if that can't be found out
+pub fn terminal_width() -> usize {
+    match ratatui::crossterm::terminal::size() {
+        Ok((columns, _)) => columns as usize,
+        Err(_) => std::env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok()).unwrap_or(80),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::Markers;
+
+    fn card(kind: CardKind, color: Option<Color>, number: Option<u8>) -> Card {
+        Card { id: 0, kind, color, number, other_side: None }
+    }
+
+    #[test]
+    fn symbols() {
+        assert_eq!(symbol(&card(CardKind::Number, Some(Color::Red), Some(7))), "7");
+        assert_eq!(symbol(&card(CardKind::Skip, Some(Color::Red), None)), "⊘");
+        assert_eq!(symbol(&card(CardKind::Reverse, Some(Color::Red), None)), "⇄");
+        assert_eq!(symbol(&card(CardKind::Draw2, Some(Color::Red), None)), "+2");
+        assert_eq!(symbol(&card(CardKind::Draw4, None, None)), "+4");
+        assert_eq!(symbol(&card(CardKind::WildDraw10, None, None)), "+10");
+    }
+
+    #[test]
+    fn cards_are_the_same_width() {
+        let letters = Theme { markers: Markers::Letters, ..Theme::default() };
+        for theme in [Theme::default(), letters] {
+            for kind in [CardKind::Number, CardKind::WildDraw10, CardKind::WildReverseDraw4, CardKind::SkipEveryone] {
+                let lines = card_lines(&card(kind, Some(Color::Purple), Some(3)), &theme);
+                assert!(lines.iter().all(|line| line.chars().count() == CARD_WIDTH));
+            }
+        }
+    }
+
+    #[test]
+    fn hand_layout() {
+        let mut hand = Hand::empty();
+        (0..12).for_each(|number| hand.push(card(CardKind::Number, Some(Color::Green), Some(number % 10))));
+        let theme = Theme::default();
+        assert!(render_hand(&hand, |_| false, MIN_ART_WIDTH - 1, ColorDepth::Ansi256, &theme).is_none());
+        // 80 columns fit 10 cards a row, so 12 cards take 2 rows of 6 lines each
+        let art = render_hand(&hand, |_| false, 80, ColorDepth::TrueColor, &theme).unwrap();
+        assert_eq!(art.lines().count(), 1 + 2 * 6);
+        assert!(art.contains("[12]"));
+        assert!(art.contains("\x1b[38;2;"));
+        let no_color = Theme { use_color: false, ..theme };
+        assert!(!render_hand(&hand, |_| false, 80, ColorDepth::TrueColor, &no_color).unwrap().contains('\x1b'));
+    }
+}
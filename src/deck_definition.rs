@@ -0,0 +1,237 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use serde::{Serialize, Deserialize};
+
+use crate::card::{CardKind, Color};
+
+/// Describes what goes into one deck. Files can be written in TOML or JSON, eg:
+/// ```toml
+/// colors = ["Red", "Green", "Blue", "Yellow"]
+/// numbers = [1, 2, 2, 2, 2, 2, 2, 2, 2, 2] # copies of 0..=9, per color
+///
+/// [actions] # copies per color
+/// Skip = 2
+/// Reverse = 2
+/// Draw2 = 2
+///
+/// [wilds] # copies in the whole deck
+/// Wild = 4
+/// Draw4 = 4
+///
+/// [per_color.Blue] # counts that differ for one color
+/// numbers = [0, 2, 2, 2, 2, 2, 2, 2, 2, 2]
+/// actions = { Draw2 = 4 }
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DeckDefinition {
+    #[serde(default = "all_colors")]
+    pub colors: Vec<Color>,
+    #[serde(default)]
+    pub numbers: [usize; 10],
+    #[serde(default)]
+    pub actions: HashMap<CardKind, usize>,
+    #[serde(default)]
+    pub wilds: HashMap<CardKind, usize>,
+    #[serde(default)]
+    pub per_color: HashMap<Color, ColorCounts>,
+}
+
+/// What goes into a deck for one color, when it differs from the other colors
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ColorCounts {
+    /// Replaces `numbers`
+    pub numbers: Option<[usize; 10]>,
+    /// Replaces the counts of the action cards listed, the others are as in `actions`
+    #[serde(default)]
+    pub actions: HashMap<CardKind, usize>,
+}
+
+fn all_colors() -> Vec<Color> {
+    Color::LIGHT.to_vec()
+}
+
+impl DeckDefinition {
+    /// The official 108 card deck
+    pub fn standard() -> DeckDefinition {
+        DeckDefinition {
+            colors: all_colors(),
+            numbers: [1, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+            actions: HashMap::from([(CardKind::Skip, 2), (CardKind::Reverse, 2), (CardKind::Draw2, 2)]),
+            wilds: HashMap::from([(CardKind::Wild, 4), (CardKind::Draw4, 4)]),
+            per_color: HashMap::new(),
+        }
+    }
+
+    /// Light side of the Uno Flip deck (112 cards)
+    pub fn flip_light() -> DeckDefinition {
+        DeckDefinition {
+            colors: Color::LIGHT.to_vec(),
+            numbers: [0, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+            actions: HashMap::from([(CardKind::Skip, 2), (CardKind::Reverse, 2), (CardKind::Draw1, 2), (CardKind::Flip, 2)]),
+            wilds: HashMap::from([(CardKind::Wild, 4), (CardKind::WildDraw2, 4)]),
+            per_color: HashMap::new(),
+        }
+    }
+
+    /// Dark side of the Uno Flip deck (112 cards)
+    pub fn flip_dark() -> DeckDefinition {
+        DeckDefinition {
+            colors: Color::DARK.to_vec(),
+            numbers: [0, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+            actions: HashMap::from([(CardKind::SkipEveryone, 2), (CardKind::Reverse, 2), (CardKind::Draw5, 2), (CardKind::Flip, 2)]),
+            wilds: HashMap::from([(CardKind::Wild, 4), (CardKind::WildDrawColor, 4)]),
+            per_color: HashMap::new(),
+        }
+    }
+
+    /// The Uno No Mercy deck (168 cards)
+    pub fn no_mercy() -> DeckDefinition {
+        DeckDefinition {
+            colors: Color::LIGHT.to_vec(),
+            numbers: [2; 10],
+            actions: HashMap::from([(CardKind::Draw2, 3), (CardKind::ColoredDraw4, 2), (CardKind::Skip, 3), (CardKind::Reverse, 3), (CardKind::SkipEveryone, 2), (CardKind::DiscardAll, 3)]),
+            wilds: HashMap::from([(CardKind::WildReverseDraw4, 8), (CardKind::WildDraw6, 4), (CardKind::WildDraw10, 4), (CardKind::WildColorRoulette, 8)]),
+            per_color: HashMap::new(),
+        }
+    }
+
+    /// Reads a definition from a `.toml` or `.json` file and validates it
+    pub fn load(path: &Path) -> Result<DeckDefinition, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read deck definition {}: {}", path.display(), e))?;
+        let definition : DeckDefinition = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&contents).map_err(|e| e.to_string()),
+            Some("json") => serde_json::from_str(&contents).map_err(|e| e.to_string()),
+            _ => Err("Deck definitions must be .toml or .json files".to_string()),
+        }.map_err(|e| format!("Invalid deck definition {}: {}", path.display(), e))?;
+        definition.validate()?;
+        Ok(definition)
+    }
+
+    /// Checks that the definition describes a deck that a game can be played with
+    pub fn validate(&self) -> Result<(), String> {
+        if self.colors.is_empty() {
+            return Err("A deck needs at least one color".to_string());
+        }
+        for (idx, color) in self.colors.iter().enumerate() {
+            if self.colors[..idx].contains(color) {
+                return Err(format!("{} is listed more than once in colors", color));
+            }
+        }
+        for (color, counts) in &self.per_color {
+            if !self.colors.contains(color) {
+                return Err(format!("{} has its own counts but is not listed in colors", color));
+            }
+            if let Some(kind) = counts.actions.keys().find(|kind| !kind.is_colored_action()) {
+                return Err(format!("{} cannot be an action card, it is not a colored power card", kind));
+            }
+        }
+        for kind in self.actions.keys() {
+            if !kind.is_colored_action() {
+                return Err(format!("{} cannot be an action card, it is not a colored power card", kind));
+            }
+        }
+        for kind in self.wilds.keys() {
+            if !kind.is_wild() {
+                return Err(format!("{} cannot be a wild card", kind));
+            }
+        }
+        if self.colored_cards() == 0 {
+            return Err("A deck needs at least one colored card to start the game with".to_string());
+        }
+        Ok(())
+    }
+
+    /// Copies of 0..=9 in `color`
+    pub fn numbers_for(&self, color: Color) -> &[usize; 10] {
+        self.per_color.get(&color).and_then(|counts| counts.numbers.as_ref()).unwrap_or(&self.numbers)
+    }
+
+    /// Copies of every action card in `color`
+    pub fn actions_for(&self, color: Color) -> HashMap<CardKind, usize> {
+        let mut actions = self.actions.clone();
+        if let Some(counts) = self.per_color.get(&color) {
+            actions.extend(counts.actions.iter().map(|(kind, &copies)| (kind.clone(), copies)));
+        }
+        actions
+    }
+
+    /// Number of cards (per deck) that have a color of their own
+    pub fn colored_cards(&self) -> usize {
+        self.colors.iter().map(|&color| self.numbers_for(color).iter().sum::<usize>() + self.actions_for(color).values().sum::<usize>()).sum()
+    }
+
+    pub fn total_cards(&self) -> usize {
+        self.colored_cards() + self.wilds.values().sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standard_definition_is_valid() {
+        let definition = DeckDefinition::standard();
+        assert!(definition.validate().is_ok());
+        assert_eq!(definition.total_cards(), 108);
+    }
+
+    #[test]
+    fn flip_definitions_are_valid() {
+        let light = DeckDefinition::flip_light();
+        let dark = DeckDefinition::flip_dark();
+        assert!(light.validate().is_ok());
+        assert!(dark.validate().is_ok());
+        assert_eq!(light.total_cards(), 112);
+        assert_eq!(dark.total_cards(), 112);
+    }
+
+    #[test]
+    fn no_mercy_definition_is_valid() {
+        let definition = DeckDefinition::no_mercy();
+        assert!(definition.validate().is_ok());
+        assert_eq!(definition.total_cards(), 168);
+    }
+
+    #[test]
+    fn example_decks_are_valid() {
+        for contents in [include_str!("../decks/no_wilds.toml"), include_str!("../decks/double_actions.toml"), include_str!("../decks/kids.toml"), include_str!("../decks/uneven_colors.toml")] {
+            let definition : DeckDefinition = toml::from_str(contents).unwrap();
+            assert!(definition.validate().is_ok());
+        }
+    }
+
+    #[test]
+    fn per_color_counts() {
+        let definition : DeckDefinition = toml::from_str(include_str!("../decks/uneven_colors.toml")).unwrap();
+        assert_eq!(definition.numbers_for(Color::Blue)[0], 0);
+        assert_eq!(definition.numbers_for(Color::Red)[0], 1);
+        assert_eq!(definition.actions_for(Color::Blue)[&CardKind::Draw2], 4);
+        assert_eq!(definition.actions_for(Color::Blue)[&CardKind::Skip], 2);
+        assert_eq!(definition.actions_for(Color::Yellow)[&CardKind::Skip], 0);
+        assert_eq!(definition.total_cards(), 108 - 1 + 2 - 2);
+        let unlisted_color : DeckDefinition = toml::from_str("colors = [\"Red\"]\nnumbers = [1, 1, 1, 1, 1, 1, 1, 1, 1, 1]\n[per_color.Blue]\nnumbers = [1, 1, 1, 1, 1, 1, 1, 1, 1, 1]").unwrap();
+        assert!(unlisted_color.validate().is_err());
+    }
+
+    #[test]
+    fn json_definition() {
+        let definition : DeckDefinition = serde_json::from_str(r#"{"numbers": [1, 1, 1, 1, 1, 1, 1, 1, 1, 1], "wilds": {"Wild": 2}}"#).unwrap();
+        assert!(definition.validate().is_ok());
+        assert_eq!(definition.total_cards(), 42);
+    }
+
+    #[test]
+    fn invalid_definitions() {
+        let only_wilds : DeckDefinition = toml::from_str("[wilds]\nWild = 10").unwrap();
+        assert!(only_wilds.validate().is_err());
+        let wild_as_action : DeckDefinition = toml::from_str("numbers = [1, 1, 1, 1, 1, 1, 1, 1, 1, 1]\n[actions]\nWild = 1").unwrap();
+        assert!(wild_as_action.validate().is_err());
+        let repeated_color : DeckDefinition = toml::from_str("colors = [\"Red\", \"Red\"]\nnumbers = [1, 1, 1, 1, 1, 1, 1, 1, 1, 1]").unwrap();
+        assert!(repeated_color.validate().is_err());
+        assert!(toml::from_str::<DeckDefinition>("jokers = 2").is_err());
+    }
+}
@@ -1,77 +1,532 @@
-use core::panic;
+use std::{collections::VecDeque, fmt};
+
+use rand::{thread_rng, seq::SliceRandom};
+use serde::{Serialize, Deserialize};
 
 use crate::card::*;
+use crate::rules::{RuleSet, RulesKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Positive,
+    Negative,
+}
+
+impl Direction {
+    pub fn flip(&mut self) {
+        *self = match *self {
+            Direction::Positive => Direction::Negative,
+            Direction::Negative => Direction::Positive,
+        }
+    }
+}
+
+/// What the game knows about a seat at the table, regardless of who (or what) sits in it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerState {
+    pub hand: Hand,
+    pub is_active: bool,
+    /// Knocked out (eg: by the mercy rule in No Mercy), as opposed to having won
+    pub is_eliminated: bool,
+}
+
+/// What happened to the player after their move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveOutcome {
+    Continue,
+    /// The player got rid of their last card
+    Won,
+    /// The player is out of the game without having won
+    Eliminated,
+}
+
+/// Why a move was rejected. Sent to the client, which decides how to word it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TurnMoveError {
+    NotYourTurn,
+    /// The card isn't in the player's hand (any more)
+    CardNotInHand,
+    /// A number card that matches neither the color nor the number of the top card
+    WrongColorOrNumber,
+    /// A power card that matches neither the color nor the kind of the top card
+    WrongColorOrKind,
+    /// There are cards to pick up and the card played doesn't pass them on
+    MustAnswerDrawStack {card_debt: usize},
+    /// No Mercy: only a draw card worth at least `min_value` can be stacked
+    DrawCardTooWeak {min_value: usize},
+    /// Stacking isn't allowed, the player has to pick the cards up
+    MustPickUp {card_debt: usize},
+    /// Hit by a Wild Draw Color or a Wild Color Roulette
+    MustDrawUntilColor(Color),
+    /// Official rules: a Draw4 can't be played while holding a card of the current color
+    Draw4WithMatchingColor,
+    MissingColorChoice,
+    /// A color was sent along with a card that isn't a wild
+    ColorChoiceOnNonWild,
+    WildColorNotInPlay {colors: Vec<Color>},
+    MissingSwapTarget,
+    SwapWithSelf,
+    InvalidTarget,
+    /// A player was sent along with a card that doesn't target anyone
+    UnexpectedTarget,
+    /// The client sent something other than a move
+    UnexpectedPacket,
+    /// The server paused the game
+    GamePaused,
+}
+
+impl fmt::Display for TurnMoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TurnMoveError::NotYourTurn => write!(f, "It is not your turn."),
+            TurnMoveError::CardNotInHand => write!(f, "That card is not in your hand."),
+            TurnMoveError::WrongColorOrNumber => write!(f, "That card matches neither the color nor the number of the topmost card."),
+            TurnMoveError::WrongColorOrKind => write!(f, "That card matches neither the color nor the kind of the topmost card."),
+            TurnMoveError::MustAnswerDrawStack { card_debt } => write!(f, "Answer with a draw card to pass on the {} cards, or pick them up.", card_debt),
+            TurnMoveError::DrawCardTooWeak { min_value } => write!(f, "You can only stack a draw card worth {} or more, or pick up the cards.", min_value),
+            TurnMoveError::MustPickUp { card_debt } => write!(f, "You have to pick up the {} cards.", card_debt),
+            TurnMoveError::MustDrawUntilColor(color) => write!(f, "You have to draw until you get a {} card.", color),
+            TurnMoveError::Draw4WithMatchingColor => write!(f, "A Draw4 can only be played when you have no card of the current color."),
+            TurnMoveError::MissingColorChoice => write!(f, "You must include a color when choosing a wild card."),
+            TurnMoveError::ColorChoiceOnNonWild => write!(f, "Only wild cards can be given a color."),
+            TurnMoveError::WildColorNotInPlay { colors } => write!(f, "Choose one of {:?} for the wild.", colors),
+            TurnMoveError::MissingSwapTarget => write!(f, "Choose a player to swap hands with (eg: `3 2` to swap with player 2)."),
+            TurnMoveError::SwapWithSelf => write!(f, "You cannot swap hands with yourself."),
+            TurnMoveError::InvalidTarget => write!(f, "That player is not in the game."),
+            TurnMoveError::UnexpectedTarget => write!(f, "That card doesn't target another player."),
+            TurnMoveError::UnexpectedPacket => write!(f, "Expected a card or 'p'."),
+            TurnMoveError::GamePaused => write!(f, "The game is paused, wait for it to be resumed."),
+        }
+    }
+}
+
+/// What every player can see of the table, which is enough to tell whether a card can be played
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableView {
+    pub top_card: Card,
+    pub card_debt: usize,
+    pub draw_until_color: Option<Color>,
+    pub dark_side: bool,
+}
+
+/// What everyone can see about a player
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerSummary {
+    pub name: String,
+    pub num_cards: usize,
+    pub is_active: bool,
+    pub is_eliminated: bool,
+    /// Round trip time to the server, filled in by the server when it knows it
+    pub latency_ms: Option<u64>,
+}
+
+/// Everything one player is allowed to know about the game, for the client to show however it likes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameSnapshot {
+    pub rules: RulesKind,
+    pub players: Vec<PlayerSummary>,
+    /// Index of the player the snapshot is for, None for spectators
+    pub you: Option<usize>,
+    pub turn: usize,
+    pub direction: Direction,
+    /// The top card (whose color is the current color), pending debt and the side in play
+    pub table: TableView,
+    /// The colors a wild can be given right now
+    pub wild_colors: Vec<Color>,
+    pub hand: Hand,
+}
+
+impl GameSnapshot {
+    pub fn is_my_turn(&self) -> bool {
+        self.you == Some(self.turn)
+    }
+}
 
-macro_rules! game_logic_bug_panic {
-    () => {
-        panic!("Game Logic Error: Please report that this happend")
-    };
+/// The state of one game of Uno, without anything network related. All rules specific decisions
+/// are left to a RuleSet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameState {
+    pub deck: Deck,
+    /// The discard pile, the topmost card is at the front
+    pub stack: VecDeque<Card>,
+    pub players: Vec<PlayerState>,
+    /// Number between 0 and players.len() (non inclusive).
+    pub turn: usize,
+    /// Two directions in which the game goes. Changes when reverse card is used
+    pub direction: Direction,
+    pub card_debt: usize,
+    pub skip_debt: usize,
+    /// Set by a Wild Draw Color or a Wild Color Roulette: the next player draws until they get this color
+    pub draw_until_color: Option<Color>,
+    /// Whether the dark side of the cards is in play (Uno Flip)
+    pub dark_side: bool,
+    /// The player whose turn it is drew until they got a card they can play (No Mercy). Picking
+    /// up again passes the turn rather than drawing more.
+    #[serde(default)]
+    pub drew_playable: bool,
 }
 
-/// Remember: top_card and player_card will have a color at this point.
-pub fn verify_move(player_card: Card, top_card: Card, card_debt: usize, ) -> Result<(), String> {
-    if card_debt > 0 {
-        match (top_card.color, top_card.kind, player_card.color, player_card.kind) {
-            (Some(_), CardKind::Draw2, Some(_), CardKind::Draw2 | CardKind::Draw4) => Ok(()),
-            (Some(t_c), CardKind::Draw4, Some(p_c), CardKind::Draw2) if p_c == t_c => Ok(()),
-            (Some(_), CardKind::Draw4, Some(_), CardKind::Draw4) => Ok(()),
-            (None, _, None, _) => game_logic_bug_panic!(),
-            _ => Err("Cannot play this card.".to_string()),
-        }
-    }
-    else {
-        // match (player_card.kind, top_card.kind) {
-        //     (CardKind::Draw4 | CardKind::Wild, _) => Ok(()),
-        // }
-        match (top_card.color, player_card.color, top_card.kind, player_card.kind, top_card.number, player_card.number) {
-            // // If top card is Draw2 or Draw4 but card_debt == 0, then logic_bug!!
-            // (_, _, CardKind::Draw2 | CardKind::Draw4, _, _, _) => game_logic_bug_panic!(), 
-            // If colors are same, can play any kind
-            (Some(t_c), Some(p_c), _, _, _, _) if t_c == p_c => Ok(()), 
-            // If kinds are same but not numbers
-            (Some(_), Some(_), t_k, p_k, None, None) if t_k == p_k => Ok(()),
-            // if player card is Draw2 or Draw4
-            (Some(_), Some(_), _, CardKind::Wild | CardKind::Draw4, _, _) => Ok(()),
-            // if top card and player card is number kind and numbers are same
-            (_, _, CardKind::Number, CardKind::Number, Some(t_n), Some(p_n)) if t_n == p_n => Ok(()),
-            _ => Err("Cannot play this card.".to_string()),
-        }
-    }
-    // match player_card.kind {
-    //     CardKind::Draw4 => Ok(()),
-    //     CardKind::Wild if card_debt != 0 => Ok(()),
-    //     _ => {
-    //         match top_card.kind {
-    //             CardKind::Number if card_debt == 0 => {
-    //                 match (top_card.color, top_card.number, player_card.color, player_card.number) {
-    //                     (Some(t_c), Some(_), Some(p_c), Some(_)) if t_c == p_c => Ok(()),
-    //                     (Some(_), Some(t_n), Some(_), Some(p_n)) if t_n == p_n => Ok(()),
-    //                     _ => Err(TurnMoveError::WrongColorOrNumberError)
-    //                 }
-    //             }
-    //             CardKind::Number if card_debt != 0 => Err(TurnMoveError::NumberCardOnCardDebtError),
-    //             CardKind::Skip | CardKind::Reverse if card_debt == 0 => {
-    //                 match (top_card.color, top_card.kind, player_card.color, player_card.kind) {
-    //                     (Some(t_c), _, Some(p_c), _) if t_c == p_c => Ok(()),
-    //                     (Some(_), t_k, Some(_), p_k) if t_k == p_k => Ok(()),
-    //                     _ => Err(TurnMoveError::WrongColorOrKindError),
-    //                 }
-    //             }
-    //             CardKind::Skip | CardKind::Reverse if card_debt != 0 => Err(TurnMoveError::NumberCardOnCardDebtError),
-    //             CardKind::Draw2 => match player_card.kind {
-    //                 CardKind::Draw2 | CardKind::Draw4 => Ok(()),
-    //                 _ => Err(TurnMoveError::WrongKindError)
-    //             }
-    //             
-    //             CardKind::Draw4 => match (player_card.kind, top_card.color) {
-    //                 (CardKind::Draw2, Some(t_c)) if t_c == player_card.color.unwrap() => Ok(()),
-    //                 (CardKind::Draw4, Some(_)) => Ok(()),
-    //                 _ => Err(TurnMoveError::WrongKindError),
-    //             }
-    //             CardKind::Wild if card_debt == 0 && top_card.color.unwrap() => Ok(()),
-    //             // CardKind::Wild if card_debt != 0 => Err(TurnMoveError::NumberCardOnCardDebtError),
-    //             _ => game_logic_error_panic!(),
-    //         }
-    //     }
-    // }
+impl GameState {
+    /// Builds the deck, deals every player's hand and turns the first card
+    pub fn new(rules: &dyn RuleSet, num_players: usize, num_decks: usize) -> GameState {
+        let mut deck = rules.build_deck(num_decks);
+        let players = (0..num_players).map(|_| PlayerState {
+            hand: Hand::new(7, &mut deck), //TODO: let users decide how many cards to start with
+            is_active: true,
+            is_eliminated: false,
+        }).collect();
+
+        // A deck guarantees cards the game can start with exist, but they could all have been
+        // dealt; in that case any card is turned, and a wild is given a random color.
+        let stack_card = match deck.pop_random_card_where(|card| rules.can_start_with(card)) {
+            Some(card) => card,
+            None => {
+                let mut card = deck.pop_random_card();
+                if card.kind.is_wild() {
+                    card.set_draw4_or_wild_color(*Color::LIGHT.choose(&mut thread_rng()).unwrap());
+                }
+                card
+            }
+        };
+
+        let mut state = GameState {
+            deck,
+            stack: VecDeque::from(vec![stack_card.clone()]),
+            players,
+            turn: 0,
+            direction: Direction::Positive,
+            card_debt: 0,
+            skip_debt: 0,
+            draw_until_color: None,
+            dark_side: false,
+            drew_playable: false,
+        };
+        rules.apply_card_effect(&mut state, &stack_card);
+        state
+    }
+
+    pub fn top_card(&self) -> &Card {
+        self.stack.front().unwrap()
+    }
+
+    pub fn table_view(&self) -> TableView {
+        TableView {
+            top_card: self.top_card().clone(),
+            card_debt: self.card_debt,
+            draw_until_color: self.draw_until_color,
+            dark_side: self.dark_side,
+        }
+    }
+
+    /// What `player` gets to see. `names` are in the same order as the players.
+    pub fn snapshot(&self, rules: &dyn RuleSet, player: Option<usize>, names: &[String]) -> GameSnapshot {
+        GameSnapshot {
+            rules: rules.kind(),
+            players: self.players.iter().zip(names).map(|(player, name)| PlayerSummary {
+                name: name.clone(),
+                num_cards: player.hand.len(),
+                is_active: player.is_active,
+                is_eliminated: player.is_eliminated,
+                latency_ms: None,
+            }).collect(),
+            you: player,
+            turn: self.turn,
+            direction: self.direction,
+            table: self.table_view(),
+            wild_colors: rules.wild_colors(self),
+            // Spectators only get to see what is on the table
+            hand: player.map_or_else(Hand::empty, |player| self.players[player].hand.clone()),
+        }
+    }
+
+    pub fn current_hand(&self) -> &Hand {
+        &self.players[self.turn].hand
+    }
+
+    pub fn active_players(&self) -> usize {
+        self.players.iter().filter(|player| player.is_active).count()
+    }
+
+    /// Takes a card from the deck. When the deck runs dry, every card of the stack except the
+    /// topmost one is shuffled back into it. Returns None only if all cards are in players' hands.
+    pub fn draw_card(&mut self) -> Option<Card> {
+        if self.deck.is_empty() && self.stack.len() > 1 {
+            let discarded = self.stack.split_off(1);
+            self.deck.refill(discarded);
+        }
+        self.deck.try_pop_random_card()
+    }
+
+    /// Draws up to `amount` cards into the hand of the current player
+    pub fn draw_cards_for_current(&mut self, amount: usize) {
+        for _ in 0..amount {
+            // If every card is in someone's hand there is nothing left to pick up
+            let Some(card) = self.draw_card() else {break};
+            self.players[self.turn].hand.push(card);
+        }
+    }
+
+    /// Goes to the next player after accounting for skip_debt, direction, and inactive players
+    pub fn advance_turn(&mut self) {
+        self.drew_playable = false;
+        let rhs = match self.direction {
+            Direction::Positive => 1,
+            Direction::Negative => -1,
+        };
+        loop {
+            self.turn = (self.turn as isize + rhs).rem_euclid(self.players.len() as isize) as usize;
+            if !self.players[self.turn].is_active {
+                continue;
+            }
+            if self.skip_debt > 0 {
+                self.skip_debt -= 1;
+                continue;
+            }
+            break;
+        }
+    }
+
+    /// Turns every card in the game over (Uno Flip)
+    pub fn flip(&mut self) {
+        self.dark_side = !self.dark_side;
+        let side_colors = if self.dark_side {Color::DARK} else {Color::LIGHT};
+        self.deck.flip();
+        self.stack.iter_mut().for_each(Card::flip);
+        for player in self.players.iter_mut() {
+            player.hand.flip();
+        }
+        // The card that is now on top may be a wild, which needs a color to be played on
+        let top_card = self.stack.front_mut().unwrap();
+        if top_card.kind.is_wild() {
+            top_card.set_draw4_or_wild_color(*side_colors.choose(&mut thread_rng()).unwrap());
+        }
+    }
+
+    /// The current player plays the card `card_id` from their hand. Also returns the other players
+    /// knocked out by the mercy rule, as hands can be passed on or swapped.
+    pub fn play_card(&mut self, rules: &dyn RuleSet, card_id: CardId, color_choice: Option<Color>, target_player: Option<usize>) -> Result<(MoveOutcome, Vec<usize>), TurnMoveError> {
+        let player = self.turn;
+        let mut card = self.players[player].hand.get(card_id).ok_or(TurnMoveError::CardNotInHand)?.clone();
+        match color_choice {
+            Some(_) if !card.kind.is_wild() => return Err(TurnMoveError::ColorChoiceOnNonWild),
+            Some(color) => card.set_draw4_or_wild_color(color), // In case Wild or Draw4, need to set color
+            None => {},
+        }
+        if target_player.is_some() && !rules.takes_target(&card) {
+            return Err(TurnMoveError::UnexpectedTarget);
+        }
+        rules.verify_wild_color(self, &card)?;
+        rules.verify_move(self, &card, target_player)?;
+
+        self.players[player].hand.remove(card_id);
+        self.stack.push_front(card.clone());
+        rules.apply_card_effect(self, &card);
+        // If that was the last card the player has won, there is nothing to pass on or swap
+        if !self.players[player].hand.is_empty() {
+            rules.apply_hand_effect(self, &card, target_player);
+        }
+        // A Discard All can get rid of the last cards as well
+        let won = self.players[player].hand.is_empty();
+        if won {
+            self.players[player].is_active = false;
+        }
+        rules.next_player(self);
+        let mut eliminated = self.eliminate_over_limit(rules);
+        let outcome = if won {
+            MoveOutcome::Won
+        }
+        else if eliminated.contains(&player) {
+            MoveOutcome::Eliminated
+        }
+        else {
+            MoveOutcome::Continue
+        };
+        eliminated.retain(|&other| other != player);
+        Ok((outcome, eliminated))
+    }
+
+    /// The current player picks up cards instead of playing
+    pub fn pick(&mut self, rules: &dyn RuleSet) -> MoveOutcome {
+        let player = self.turn;
+        let keeps_turn = rules.pick_up(self);
+        self.card_debt = 0;
+        if rules.is_eliminated(self.players[player].hand.len()) {
+            self.eliminate(rules, player);
+            return MoveOutcome::Eliminated;
+        }
+        if !keeps_turn {
+            rules.next_player(self);
+        }
+        MoveOutcome::Continue
+    }
+
+    /// Knocks out everyone holding more cards than the rules allow, and returns who they were
+    fn eliminate_over_limit(&mut self, rules: &dyn RuleSet) -> Vec<usize> {
+        let over_limit = (0..self.players.len())
+            .filter(|&player| self.players[player].is_active && rules.is_eliminated(self.players[player].hand.len()))
+            .collect::<Vec<usize>>();
+        for &player in &over_limit {
+            self.eliminate(rules, player);
+        }
+        over_limit
+    }
+
+    /// A player is out of the game (eg: they left, or broke the mercy rule). Their cards go back
+    /// into the deck, and if it was their turn, the turn moves on.
+    pub fn eliminate(&mut self, rules: &dyn RuleSet, player: usize) {
+        let cards = self.players[player].hand.take_all();
+        self.deck.refill(cards);
+        self.players[player].is_active = false;
+        self.players[player].is_eliminated = true;
+        if player != self.turn {
+            return;
+        }
+        // Whatever the player owed is not passed on to the next one
+        self.card_debt = 0;
+        self.draw_until_color = None;
+        if self.active_players() > 0 {
+            rules.next_player(self);
+        }
+    }
+
+    /// If game is over, returns Some(id) of the last player left, otherwise returns None
+    pub fn is_game_over(&self) -> Option<usize> {
+        match self.active_players() {
+            1 => self.players.iter().position(|player| player.is_active),
+            _ => None,
+        }
+    }
+
+    /// Whether the last player left won, because everyone else was eliminated rather than going out
+    pub fn last_one_standing(&self) -> bool {
+        self.players.iter().all(|player| player.is_active || player.is_eliminated)
+    }
+
+    /// Points left in a player's hand
+    pub fn hand_points(&self, rules: &dyn RuleSet, player: usize) -> usize {
+        self.players[player].hand.iter().map(|card| rules.card_points(card)).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::RulesKind;
+    use crate::deck_definition::DeckDefinition;
+
+    #[test]
+    fn dealing() {
+        let rules = RulesKind::House.rule_set(DeckDefinition::standard());
+        let state = GameState::new(rules.as_ref(), 4, 1);
+        assert_eq!(state.players.len(), 4);
+        assert!(state.players.iter().all(|player| player.hand.len() == 7));
+        assert_eq!(state.deck.len(), 108 - 4 * 7 - 1);
+        assert!(!state.top_card().kind.is_wild());
+    }
+
+    #[test]
+    fn turns_skip_inactive_players() {
+        let rules = RulesKind::House.rule_set(DeckDefinition::standard());
+        let mut state = GameState::new(rules.as_ref(), 4, 1);
+        state.direction = Direction::Positive;
+        state.skip_debt = 0;
+        state.turn = 0;
+        state.players[1].is_active = false;
+        state.advance_turn();
+        assert_eq!(state.turn, 2);
+        state.skip_debt = 1;
+        state.advance_turn();
+        assert_eq!(state.turn, 0);
+        state.direction.flip();
+        state.advance_turn();
+        assert_eq!(state.turn, 3);
+    }
+
+    #[test]
+    fn picking_up_pays_off_the_debt() {
+        let rules = RulesKind::House.rule_set(DeckDefinition::standard());
+        let mut state = GameState::new(rules.as_ref(), 2, 1);
+        state.turn = 0;
+        state.skip_debt = 0;
+        state.card_debt = 4;
+        assert_eq!(state.pick(rules.as_ref()), MoveOutcome::Continue);
+        assert_eq!(state.players[0].hand.len(), 11);
+        assert_eq!(state.card_debt, 0);
+        assert_eq!(state.turn, 1);
+    }
+
+    #[test]
+    fn game_over() {
+        let rules = RulesKind::House.rule_set(DeckDefinition::standard());
+        let mut state = GameState::new(rules.as_ref(), 3, 1);
+        assert_eq!(state.is_game_over(), None);
+        state.players[0].is_active = false;
+        state.players[2].is_active = false;
+        state.players[2].is_eliminated = true;
+        assert_eq!(state.is_game_over(), Some(1));
+        assert!(!state.last_one_standing());
+        state.players[0].is_eliminated = true;
+        assert!(state.last_one_standing());
+    }
+
+    #[test]
+    fn snapshot_hides_other_hands() {
+        let rules = RulesKind::House.rule_set(DeckDefinition::standard());
+        let state = GameState::new(rules.as_ref(), 3, 1);
+        let names = ["a", "b", "c"].map(String::from);
+        let snapshot = state.snapshot(rules.as_ref(), Some(1), &names);
+        assert_eq!(snapshot.you, Some(1));
+        assert_eq!(snapshot.players[2].name, "c");
+        assert_eq!(snapshot.players[2].num_cards, 7);
+        assert!(snapshot.hand.iter().all(|card| state.players[1].hand.get(card.id).is_some()));
+        let spectator = state.snapshot(rules.as_ref(), None, &names);
+        assert!(spectator.hand.is_empty());
+        assert!(!spectator.is_my_turn());
+    }
+
+    #[test]
+    fn card_not_in_hand() {
+        let rules = RulesKind::House.rule_set(DeckDefinition::standard());
+        let mut state = GameState::new(rules.as_ref(), 2, 1);
+        let other_players_card = state.players[1 - state.turn].hand.get_at(1).id;
+        assert_eq!(state.play_card(rules.as_ref(), other_players_card, None, None), Err(TurnMoveError::CardNotInHand));
+        assert_eq!(state.play_card(rules.as_ref(), 1000, None, None), Err(TurnMoveError::CardNotInHand));
+    }
+
+    #[test]
+    fn untrusted_move_fields() {
+        let rules = RulesKind::House.rule_set(DeckDefinition::standard());
+        let mut state = GameState::new(rules.as_ref(), 2, 1);
+        state.players[0].hand = Hand::empty();
+        state.players[0].hand.push(Card { id: 1000, kind: CardKind::Number, color: Some(Color::Red), number: Some(3), other_side: None });
+        state.players[0].hand.push(Card { id: 1001, kind: CardKind::Wild, color: None, number: None, other_side: None });
+        state.turn = 0;
+        assert_eq!(state.play_card(rules.as_ref(), 1000, Some(Color::Blue), None), Err(TurnMoveError::ColorChoiceOnNonWild));
+        assert_eq!(state.play_card(rules.as_ref(), 1000, None, Some(1)), Err(TurnMoveError::UnexpectedTarget));
+        assert_eq!(state.play_card(rules.as_ref(), 1001, None, None), Err(TurnMoveError::MissingColorChoice));
+        assert_eq!(state.play_card(rules.as_ref(), 1001, Some(Color::Pink), None), Err(TurnMoveError::WildColorNotInPlay { colors: Color::LIGHT.to_vec() }));
+        assert_eq!(state.players[0].hand.len(), 2);
+    }
+
+    #[test]
+    fn eliminating_a_player() {
+        let rules = RulesKind::House.rule_set(DeckDefinition::standard());
+        let mut state = GameState::new(rules.as_ref(), 3, 1);
+        state.turn = 1;
+        state.skip_debt = 0;
+        state.direction = Direction::Positive;
+        state.card_debt = 4;
+        let deck_len = state.deck.len();
+        state.eliminate(rules.as_ref(), 1);
+        assert_eq!(state.deck.len(), deck_len + 7);
+        assert!(state.players[1].is_eliminated);
+        assert_eq!(state.card_debt, 0);
+        assert_eq!(state.turn, 2);
+        // Someone leaving out of turn changes nothing for the player whose turn it is
+        state.card_debt = 4;
+        state.eliminate(rules.as_ref(), 0);
+        assert!(state.players[0].is_eliminated);
+        assert_eq!(state.card_debt, 4);
+        assert_eq!(state.turn, 2);
+    }
 }
@@ -4,12 +4,22 @@ use clap::{command, arg, ArgGroup, value_parser};
 mod netcode;
 mod card;
 mod game;
+mod deck_definition;
+mod rules;
+mod tui;
+mod card_art;
+mod theme;
+mod bot;
+mod search;
 
 use crate::netcode::client_server;
+use crate::deck_definition::DeckDefinition;
+use crate::rules::RulesKind;
+use crate::card::HandSort;
+use crate::theme::{Markers, Palette, Theme};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // let args = Args::parse();
     let matches = command!()
         .group(ArgGroup::new("mode").required(true).multiple(false))
         .arg(
@@ -34,34 +44,190 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .conflicts_with("client")
             .default_value("8080")
             )
+        .arg(
+            arg!(-d --decks <VALUE>)
+            .help("Number of standard decks to shuffle together. If not used, one deck is added for every 10 players")
+            .value_parser(value_parser!(u32).range(1..))
+            .conflicts_with("client")
+            )
+        .arg(
+            arg!(--deck <FILE>)
+            .help("Load the composition of a deck from a TOML or JSON file (see the decks/ folder for examples)")
+            .value_parser(value_parser!(std::path::PathBuf))
+            .conflicts_with("client")
+            )
+        .arg(
+            arg!(-r --rules <RULES>)
+            .help("Which rules to play by. For clients, the rules of the room made with --create")
+            .value_parser(value_parser!(RulesKind))
+            .default_value("house")
+            )
+        .arg(
+            arg!(--"rotate-first")
+            .help("On a rematch, let the next player go first")
+            .conflicts_with("client")
+            )
+        .arg(
+            arg!(--"turn-time" <SECONDS>)
+            .help("Time players have for their move. When it runs out they pick up, and after 3 turns in a row they are marked AFK")
+            .value_parser(value_parser!(u64).range(5..))
+            .conflicts_with("client")
+            )
+        .arg(
+            arg!(--"stand-in")
+            .help("When a player runs out of time, play a card for them instead of making them pick up")
+            .requires("turn-time")
+            .conflicts_with("client")
+            )
+        .arg(
+            arg!(--"ping-interval" <SECONDS>)
+            .help("How often clients are pinged, to measure their latency and notice dead connections")
+            .value_parser(value_parser!(u64).range(1..))
+            .conflicts_with("client")
+            .default_value("5")
+            )
+        .arg(
+            arg!(--"max-missed-pongs" <COUNT>)
+            .help("Disconnect a client after this many pings in a row go unanswered")
+            .value_parser(value_parser!(u64).range(1..))
+            .conflicts_with("client")
+            .default_value("3")
+            )
+        .arg(
+            arg!(--"bot-budget" <MILLISECONDS>)
+            .help("How long expert bots think about a move, and --analyze about the position")
+            .value_parser(value_parser!(u64).range(1..))
+            .conflicts_with("client")
+            .default_value("1000")
+            )
+        .arg(
+            arg!(--"bot-time-limit" <SECONDS>)
+            .help("Time external bots (see the `spawn` console command) have for their move, after which they pick up")
+            .value_parser(value_parser!(u64).range(1..))
+            .conflicts_with("client")
+            .default_value("10")
+            )
+        .arg(
+            arg!(--analyze <FILE>)
+            .help("Suggest a move for the player whose turn it is, in a game saved with the server's `save` command. \
+                  The room is picked with --room")
+            .value_parser(value_parser!(std::path::PathBuf))
+            .group("mode")
+            )
+        .arg(
+            arg!(--sort <ORDER>)
+            .help("How to order the cards in your hand. Type 's' on your turn to switch")
+            .value_parser(value_parser!(HandSort))
+            .conflicts_with("server")
+            .default_value("dealt")
+            )
+        .arg(
+            arg!(--"auto-pick")
+            .help("Pick up automatically when none of your cards can be played")
+            .conflicts_with("server")
+            )
+        .arg(
+            arg!(--tui)
+            .help("Play in a full-screen terminal UI instead of the line by line client")
+            .conflicts_with("server")
+            )
+        .arg(
+            arg!(--"card-art")
+            .help("Draw your hand as cards side by side. Falls back to a list on narrow terminals")
+            .conflicts_with("server")
+            )
+        .arg(
+            arg!(--spectate)
+            .help("Watch the game without playing. Spectators can join a game that has already started")
+            .conflicts_with("server")
+            )
+        .arg(
+            arg!(--room <NAME>)
+            .help("The room to join, or to create with --create")
+            .conflicts_with("server")
+            .default_value(client_server::DEFAULT_ROOM)
+            )
+        .arg(
+            arg!(--create)
+            .help("Create the room given with --room, playing by the rules given with -r")
+            .conflicts_with("server")
+            )
+        .arg(
+            arg!(--"list-rooms")
+            .help("Print the rooms of the server, with their players and status, and quit")
+            .conflicts_with("server")
+            )
+        .arg(
+            arg!(--palette <PALETTE>)
+            .help("Colors to draw the cards in")
+            .value_parser(value_parser!(Palette))
+            .conflicts_with("server")
+            .default_value("standard")
+            )
+        .arg(
+            arg!(--markers <MARKERS>)
+            .help("Mark every card with its color as a letter or a shape, for colorblind players. \
+                  Letters are used anyway when NO_COLOR is set")
+            .value_parser(value_parser!(Markers))
+            .conflicts_with("server")
+            .default_value("none")
+            )
         .arg(
             arg!(-j --joincode <VALUE>)
             .help("Specify the join code. After the server owner runs the server, say they get the code \"813237\"\n \
                   You need to do `runo -c -j '813237'")
             .required(true)
-            .conflicts_with("server")
+            .conflicts_with_all(["server", "analyze"])
             )
-        // .arg(
-        //     arg!(-v --verbose)
-        //     .help("Enable verbose mode")
-        //     )
-        // .arg(
-        //     arg!("name")
-        //     .short('n')
-        //     .long("name")
-        //     // .about("Specify name (client mode only)")
-        //     .requires_if("mode", "client"),
-        //     )
         .get_matches();
 
     let port = *matches.get_one("port").unwrap();
     if *matches.get_one("server").unwrap() {
-        client_server::run_server(port).await?;
+        let rules = *matches.get_one::<RulesKind>("rules").unwrap();
+        // The deck definition is validated here so that a broken file is caught before anyone joins
+        let deck_definition = match matches.get_one::<std::path::PathBuf>("deck") {
+            Some(_) if !rules.supports_custom_decks() => return Err(format!("Custom decks cannot be used with the {:?} rules", rules).into()),
+            Some(path) => DeckDefinition::load(path)?,
+            None => rules.default_deck_definition(),
+        };
+        let config = client_server::ServerConfig {
+            port,
+            num_decks: matches.get_one::<u32>("decks").map(|num_decks| *num_decks as usize),
+            deck_definition,
+            rules,
+            rotate_first: *matches.get_one::<bool>("rotate-first").unwrap(),
+            turn_time: matches.get_one::<u64>("turn-time").map(|seconds| std::time::Duration::from_secs(*seconds)),
+            stand_in: *matches.get_one::<bool>("stand-in").unwrap(),
+            ping_interval: std::time::Duration::from_secs(*matches.get_one::<u64>("ping-interval").unwrap()),
+            max_missed_pongs: *matches.get_one::<u64>("max-missed-pongs").unwrap() as usize,
+            bot_budget: std::time::Duration::from_millis(*matches.get_one::<u64>("bot-budget").unwrap()),
+            bot_time_limit: std::time::Duration::from_secs(*matches.get_one::<u64>("bot-time-limit").unwrap()),
+        };
+        client_server::run_server(config).await?;
+    }
+
+    if let Some(path) = matches.get_one::<std::path::PathBuf>("analyze") {
+        let budget = std::time::Duration::from_millis(*matches.get_one::<u64>("bot-budget").unwrap());
+        search::analyze(path, matches.get_one::<String>("room").unwrap(), budget)?;
     }
 
     if *matches.get_one("client").unwrap() {
         let join_code : String = matches.get_one::<String>("joincode").unwrap().to_string();
-        client_server::run_client(matches.get_one("name"), join_code).await?;
+        Theme::new(*matches.get_one::<Palette>("palette").unwrap(), *matches.get_one::<Markers>("markers").unwrap()).install();
+        let config = client_server::ClientConfig {
+            hand_sort: *matches.get_one::<HandSort>("sort").unwrap(),
+            auto_pick: *matches.get_one::<bool>("auto-pick").unwrap(),
+            tui: *matches.get_one::<bool>("tui").unwrap(),
+            card_art: *matches.get_one::<bool>("card-art").unwrap(),
+            spectator: *matches.get_one::<bool>("spectate").unwrap(),
+            room: matches.get_one::<String>("room").unwrap().to_string(),
+            create_room: match *matches.get_one::<bool>("create").unwrap() {
+                true => Some(*matches.get_one::<RulesKind>("rules").unwrap()),
+                false => None,
+            },
+            list_rooms: *matches.get_one::<bool>("list-rooms").unwrap(),
+        };
+        client_server::run_client(matches.get_one("name"), join_code, config).await?;
     }
     Ok(())
 }
@@ -1,10 +1,14 @@
-use std::{net::{TcpListener, TcpStream}, io, sync::{Arc, Mutex}, collections::VecDeque, };
+use std::{net::{TcpListener, TcpStream}, io, sync::{Arc, Mutex, mpsc}, thread, time::{Duration, Instant}, collections::VecDeque};
 
-use crate::{netcode::packets::{send_packet, ServerPacket, read_packet, ClientPacket,}, card::{Deck, Hand, Color}, game::verify_move};
-use crate::netcode::misc::Names;
-use crate::card;
-
-use card::Card;
+use crate::{netcode::packets::{send_packet, ServerPacket, read_packet, ClientPacket,}, card::{Card, Color, Hand, HandSort}};
+use crate::netcode::console;
+use crate::netcode::misc::{Names, is_valid_name, parse_host_command, MAX_NAME_LEN};
+use crate::netcode::room::{next_client_id, ClientId, ClientInfo, GamePhase, Incoming, Room, RoomSettings};
+use crate::deck_definition::DeckDefinition;
+use crate::game::{Direction, GameSnapshot, TableView};
+use crate::rules::{RuleSet, RulesKind};
+use crate::card_art::{self, ColorDepth};
+use crate::theme::Theme;
 
 macro_rules! cls {
     () => {
@@ -12,12 +16,6 @@ macro_rules! cls {
     }
 }
 
-macro_rules! server_received_unexpected_packet {
-    () => {
-        bunt::println!("{$red}[{}]Server received unexpected packet from client{/$}", line!())
-    };
-}
-
 
 macro_rules! client_received_unexpected_packet {
     () => {
@@ -25,125 +23,190 @@ macro_rules! client_received_unexpected_packet {
     };
 }
 
-#[derive(Debug, PartialEq, PartialOrd)]
-enum GamePhase {
-    Waiting,
-    InGame,
-    GameOver,
-}
-
+/// Settings the server is started with, which are those of the default room
 #[derive(Debug)]
-enum Direction {
-    Positive,
-    Negative,
+pub struct ServerConfig {
+    pub port: u32,
+    /// Number of decks to play with. When None, it is picked from the number of players
+    pub num_decks: Option<usize>,
+    /// What goes into a single deck (ignored by rule sets with a fixed deck)
+    pub deck_definition: DeckDefinition,
+    pub rules: RulesKind,
+    /// On a rematch, whoever went first moves to the last seat. Applies to every room
+    pub rotate_first: bool,
+    /// How long players have for their move, in every room
+    pub turn_time: Option<Duration>,
+    /// Play for players who run out of time instead of making them pick up
+    pub stand_in: bool,
+    /// How often clients are pinged
+    pub ping_interval: Duration,
+    /// A client that leaves this many pings in a row unanswered is disconnected
+    pub max_missed_pongs: usize,
+    /// How long expert bots think about a move
+    pub bot_budget: Duration,
+    /// How long external bots have for their move
+    pub bot_time_limit: Duration,
 }
 
-impl Direction {
-    fn flip(&mut self) {
-        *self = match *self {
-            Direction::Positive => Direction::Negative,
-            Direction::Negative => Direction::Positive,
-        }
+impl ServerConfig {
+    fn room_settings(&self) -> RoomSettings {
+        RoomSettings { num_decks: self.num_decks, rotate_first: self.rotate_first, turn_time: self.turn_time, stand_in: self.stand_in, bot_budget: self.bot_budget, bot_time_limit: self.bot_time_limit }
     }
 }
 
-#[derive(Debug)]
-struct ClientInfo {
-    stream: TcpStream,
-    name: String,
-    hand: Hand,
-    is_active: bool,
-}
+/// How often the turn timers are looked at
+const TICK : Duration = Duration::from_millis(200);
+/// How long sending to a client may take. Packets are sent while the server's data is locked, so
+/// a client that stops reading is disconnected rather than waited on.
+const WRITE_TIMEOUT : Duration = Duration::from_secs(2);
 
-#[derive(Debug)]
-struct GlobalGameData {
-    names : Names,
-    game_phase: GamePhase,
-    curr_total_clients_num : usize,
-    curr_client_id_turn: usize, /// Number between 0 and curr_clients_num (non inclusive).
-    master_deck : Deck,
-    direction: Direction,
-    card_debt: usize,
-    skip_debt: usize,
-    stack: VecDeque<Card>,
-    clients_info: Vec<ClientInfo>,
+/// Name of the room set up with the server's own settings. It is always there, so that clients
+/// that don't care about rooms can simply connect.
+pub const DEFAULT_ROOM : &str = "main";
+
+/// Everything the server keeps track of, behind a single lock
+pub struct ServerData {
+    pub names : Names,
+    /// Clients that are not in a room
+    pub lobby: Vec<ClientInfo>,
+    pub rooms: Vec<Room>,
+    /// Names that are turned away when they connect (see the `ban` command)
+    pub banned: Vec<String>,
+    /// Used to set up the default room again once everyone has left it
+    pub config: ServerConfig,
 }
 
-impl GlobalGameData {
-    fn get_players_string(&self) -> String {
-        let mut ret_string = String::new();
-        for client_idx in 0..self.clients_info.len() {
-            if client_idx == self.curr_client_id_turn {
-                ret_string += "* ";
+impl ServerData {
+    pub fn new(config: ServerConfig) -> ServerData {
+        ServerData { names: Names::new(), lobby: vec![], rooms: vec![ServerData::default_room(&config)], banned: vec![], config }
+    }
+
+    fn default_room(config: &ServerConfig) -> Room {
+        Room::new(DEFAULT_ROOM.to_string(), config.rules.rule_set(config.deck_definition.clone()), config.room_settings())
+    }
+
+    /// Names given out twice get a #number, which is ignored here
+    pub fn is_banned(&self, name: &str) -> bool {
+        let base_name = name.split('#').next().unwrap_or(name);
+        self.banned.iter().any(|banned| banned == base_name)
+    }
+
+    pub fn room_mut(&mut self, name: &str) -> Option<&mut Room> {
+        self.rooms.iter_mut().find(|room| room.name == name)
+    }
+
+    pub fn tick(&mut self, now: Instant) {
+        self.rooms.iter_mut().for_each(|room| room.tick(now));
+        self.send_pings(now);
+        self.clean_up_rooms();
+    }
+
+    /// Pings everyone whose ping is due. Clients that stopped answering have their connection
+    /// closed, so that they are handled like anyone who left once their reader thread notices.
+    /// That is the only way to find out about a half-open connection, eg: a laptop put to sleep.
+    fn send_pings(&mut self, now: Instant) {
+        let (interval, max_missed_pongs) = (self.config.ping_interval, self.config.max_missed_pongs);
+        let rooms = self.rooms.iter_mut().flat_map(|room| room.clients_info.iter_mut().chain(room.spectators.iter_mut()));
+        // Bots aren't pinged, external ones have a move time limit instead
+        for client in self.lobby.iter_mut().chain(rooms).filter(|client| client.connected && !client.is_bot()) {
+            if let Some(id) = client.heartbeat.ping_due(now, interval) {
+                client.send(ServerPacket::Ping { id });
             }
-            else {
-                ret_string += "  ";
+            if client.heartbeat.is_dead(max_missed_pongs) {
+                bunt::println!("{$yellow}{} stopped answering pings{/$}", client.name);
+                client.disconnect();
             }
-            ret_string += &format!("({}) ", self.clients_info[client_idx].hand.len()).to_string();
-            ret_string += &self.clients_info[client_idx].name;
-            ret_string += "\n"
         }
-        ret_string
     }
 
-    /// Goes to the next player after accounting for skip_debt, direction, and inactive players
-    fn next_player(&mut self) {
-        fn next_client_id_wrapping(global_game_data : &mut GlobalGameData) {
-            let rhs = match global_game_data.direction {
-                Direction::Positive => 1,
-                Direction::Negative => -1,
-            };
-            global_game_data.curr_client_id_turn = 
-                (global_game_data.curr_client_id_turn as isize + rhs)
-                .rem_euclid(global_game_data.curr_total_clients_num as isize) as usize;
-        }
+    fn client_mut(&mut self, client_id: ClientId) -> Option<&mut ClientInfo> {
+        let rooms = self.rooms.iter_mut().flat_map(|room| room.clients_info.iter_mut().chain(room.spectators.iter_mut()));
+        self.lobby.iter_mut().chain(rooms).find(|client| client.id == client_id)
+    }
 
-        loop {
-            next_client_id_wrapping(self);
-            if !self.clients_info[self.curr_client_id_turn].is_active {
-                continue;
+    pub fn handle(&mut self, incoming: Incoming) {
+        // Pongs can come from anywhere, and only matter to the heartbeat
+        if let Ok(ClientPacket::Pong { id }) = incoming.packet {
+            if let Some(client) = self.client_mut(incoming.client_id) {
+                client.heartbeat.pong(id, Instant::now());
             }
-            if self.skip_debt > 0 {
-                self.skip_debt = 0;
-                continue;
-            }
-            break;
+            return;
         }
-
-        // let rhs : isize;
-        // match self.direction {
-        //     Direction::Positive => rhs = 1 + self.skip_debt as isize,
-        //     Direction::Negative => rhs = - (1 + self.skip_debt as isize),
-        // }
-        // let next_player = (lhs + rhs).rem_euclid(self.curr_total_clients_num as isize);
-        // if next_player < 0 || next_player >= self.curr_total_clients_num as isize {
-        //     next_player = next_player % self.curr_total_clients_num as isize;
-        // }
-        // self.curr_client_id_turn = next_player as usize;
-        // self.skip_debt = 0;
+        if let Some(idx) = self.lobby.iter().position(|client| client.id == incoming.client_id) {
+            self.handle_lobby(idx, incoming.packet);
+        }
+        else if let Some(room) = self.rooms.iter_mut().find(|room| room.has_client(incoming.client_id)) {
+            room.handle(incoming);
+        }
+        self.clean_up_rooms();
     }
 
-    /// If game is over, returns Some(id) of client who lost, otherwise returns None
-    fn is_game_over(&mut self) -> Option<usize> {
-        let mut inactive_clients : usize = 0;
-        let mut loser_client = 0; // last active client
-        for client_idx in 0..self.clients_info.len() {
-            let client = &self.clients_info[client_idx];
-            if !client.is_active {
-                inactive_clients += 1;
+    fn handle_lobby(&mut self, idx: usize, packet: io::Result<ClientPacket>) {
+        let reply = |server: &mut ServerData, msg: String| server.lobby[idx].send(ServerPacket::SendMsg { msg: Some(msg) });
+        match packet {
+            Err(e) => {
+                let client = self.lobby.remove(idx);
+                bunt::println!("{$yellow}{} has left ({}){/$}", client.name, e);
+            }
+            Ok(ClientPacket::ListRooms) => {
+                let rooms = self.rooms.iter().map(Room::summary).collect();
+                self.lobby[idx].send(ServerPacket::SendRoomList { rooms });
+            }
+            Ok(ClientPacket::JoinRoom { room, spectator }) => {
+                let Some(room) = self.rooms.iter_mut().find(|other| other.name == room) else {
+                    return reply(self, format!("There is no room called {}.", room));
+                };
+                if room.locked {
+                    let msg = format!("{} is locked.", room.name);
+                    return reply(self, msg);
+                }
+                // Hands are dealt once the game starts, so there is no seat for latecomers. After the
+                // game, newcomers can take part in the rematch.
+                if !spectator && room.game_phase == GamePhase::InGame {
+                    let msg = format!("The game in {} has already started, you can still join as a spectator.", room.name);
+                    return reply(self, msg);
+                }
+                room.add_client(self.lobby.remove(idx), spectator);
+            }
+            Ok(ClientPacket::CreateRoom { room, rules }) => {
+                if !is_valid_name(&room) {
+                    return reply(self, format!("Room names must be at most {} characters long and must not have whitespaces and the '#' char.", MAX_NAME_LEN));
+                }
+                if self.room_mut(&room).is_some() {
+                    return reply(self, format!("There already is a room called {}.", room));
+                }
+                bunt::println!("{$green}Room {} was created, playing by the {:?} rules{/$}", room, rules);
+                let mut new_room = Room::new(room, rules.rule_set(rules.default_deck_definition()), RoomSettings { num_decks: None, ..self.config.room_settings() });
+                new_room.add_client(self.lobby.remove(idx), false);
+                self.rooms.push(new_room);
             }
-            else {
-                loser_client = client_idx;
+            Ok(ClientPacket::SendChat { .. }) => reply(self, "Join a room to chat.".to_string()),
+            Ok(_) => {
+                server_received_unexpected_packet!();
+                reply(self, "Join a room first.".to_string());
             }
         }
-        match inactive_clients == self.clients_info.len() - 1 {
-            true => Some(loser_client),
-            false => None
+    }
+
+    /// A room goes away once everyone has left it, also in the middle of a game since nobody is
+    /// left to finish it (bots don't count). The default room is kept while it waits for players,
+    /// and set up again when it goes.
+    pub fn clean_up_rooms(&mut self) {
+        self.rooms.retain(|room| {
+            let keep = !room.is_empty() || (room.name == DEFAULT_ROOM && room.game_phase == GamePhase::Waiting);
+            if !keep {
+                bunt::println!("{$yellow}Room {} was closed{/$}", room.name);
+            }
+            keep
+        });
+        if self.room_mut(DEFAULT_ROOM).is_none() {
+            let room = Self::default_room(&self.config);
+            self.rooms.push(room);
         }
     }
 }
 
-pub async fn run_server(port : u32) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn run_server(config : ServerConfig) -> Result<(), Box<dyn std::error::Error>> {
     // let listener = ngrok::Session::builder()
     //     .authtoken_from_env()
     //     .connect()
@@ -166,179 +229,50 @@ pub async fn run_server(port : u32) -> Result<(), Box<dyn std::error::Error>> {
     // });
 
     bunt::println!("{$green}The server has been started{/$}");
-    let listener = TcpListener::bind(format!("localhost:{port}"))?;
-    let mut deck = Deck::new();
-    let mut stack_card;
-    loop {
-        stack_card = deck.pop_random_card();
-        match stack_card.kind {
-            card::CardKind::Wild | card::CardKind::Draw4 => deck.push_card(stack_card),
-            _ => break,
-        }
-    }
-    
-    let mut skip_debt = 0;
-    let mut card_debt = 0;
-    let mut direction = Direction::Positive;
-    match stack_card.kind {
-        card::CardKind::Number => {},
-        card::CardKind::Skip => skip_debt = 1,
-        card::CardKind::Reverse => direction.flip(),
-        card::CardKind::Draw2 => card_debt += 2,
-        card::CardKind::Draw4 | card::CardKind::Wild => unreachable!(),
-    }
-    let shared_global_game_data = Arc::new(Mutex::new(GlobalGameData {
-        names: Names::new(),
-        game_phase: GamePhase::Waiting,
-        curr_total_clients_num: 0, /// Total number of connected clients
-        curr_client_id_turn:0, /// Number between 0 and curr_clients_num (non inclusive).
-        master_deck: deck, /// The main deck from where cards are taken to form hands
-        direction, // Two directions in which the game goes. Changes when reverse card is used
-        card_debt,
-        skip_debt,
-        stack: VecDeque::from(vec![stack_card]),
-        clients_info: vec![],
-    }));
-
-    /// IMP: This function holds shared_state for a long time
-    async fn game_thread(shared_state: Arc<Mutex<GlobalGameData>>) {
-        let mut shrared_state_held = shared_state.lock().unwrap();
-        shrared_state_held.game_phase = GamePhase::InGame;
-        loop {
-            // provide updates to players
-            for idx in 0..shrared_state_held.clients_info.len() {
-                if !shrared_state_held.clients_info[idx].is_active {continue;}
-                let mut msg_first_half = "\nPlayers: \n".to_string() + &shrared_state_held.get_players_string() + "\n";
-                msg_first_half += &format!("Topmost card: {}\n", shrared_state_held.stack.get(0).unwrap().get_colorized_repr()).to_string();
-                let msg_second_half;
-                if shrared_state_held.card_debt > 0 {
-                    msg_second_half
-                        = format!("Type number 1-{} to choose the card at that index as indicated above in your hand. \
-                                  If choosing a Draw4 or Wild, tell the color as well (eg: `2 blue` given 2 has a Draw4 or a Wild). \
-                                  You can only choose a Draw2 or a Draw4 to make the next opponent pick up {} or {} cards respectively. \
-                                  You can also type 'p' to pick up {} cards",
-                                  shrared_state_held.clients_info[idx].hand.len(), shrared_state_held.card_debt + 2, shrared_state_held.card_debt + 4, shrared_state_held.card_debt);
-                } 
-                else {
-                    msg_second_half = format!("Type number 1-{} to choose the card at that index as indicated above in your hand. \
-                                              If choosing a Draw4 or Wild, type the chosen color as well (eg: `2 blue` given 2 has a Draw4 or a Wild). \
-                                              You can also type 'p' to pick up 1 card",
-                    shrared_state_held.clients_info[idx].hand.len());
-                };
-                let hand_copy = shrared_state_held.clients_info[idx].hand.clone();
-                let is_my_turn = idx == shrared_state_held.curr_client_id_turn;
-                send_packet(&mut shrared_state_held.clients_info[idx].stream,
-                            ServerPacket::SendMsgUpdate { msg_first_half, hand: hand_copy, msg_second_half, is_my_turn });
-            }
-            let curr_client_id = shrared_state_held.curr_client_id_turn;
-            let client_send_move_packet = read_packet::<ClientPacket>(&mut shrared_state_held.clients_info[curr_client_id].stream);
-            match client_send_move_packet {
-                ClientPacket::SendMoveCard { card_idx, color_choice } => {
-                    let mut card = shrared_state_held.clients_info[curr_client_id].hand.get_at(card_idx);
-                    if color_choice.is_some() {card.set_draw4_or_wild_color(color_choice.unwrap())}; // In case Wild or Draw4, need to set color
-                    let result = verify_move(card.clone(), shrared_state_held.stack.get(0).unwrap().clone(), shrared_state_held.card_debt);
-                    match result {
-                        Ok(_) => {
-                            match card.kind {
-                                card::CardKind::Number => {},
-                                card::CardKind::Skip => shrared_state_held.skip_debt = 1,
-                                card::CardKind::Reverse => shrared_state_held.direction.flip(),
-                                card::CardKind::Draw2 => shrared_state_held.card_debt += 2,
-                                card::CardKind::Draw4 => shrared_state_held.card_debt += 4,
-                                card::CardKind::Wild => {},
-                            }
-                            let mut card = shrared_state_held.clients_info[curr_client_id].hand.pop_at(card_idx);
-                            if color_choice.is_some() {card.set_draw4_or_wild_color(color_choice.unwrap())}; // In case Wild or Draw4, need to set color
-                            shrared_state_held.stack.push_front(card.clone());
-                            shrared_state_held.master_deck.push_card(card);
-                            shrared_state_held.next_player();
-                            send_packet(&mut shrared_state_held.clients_info[curr_client_id].stream, ServerPacket::SendMoveAcknowledgement { msg: None });
-
-                            // check if player won
-                            if shrared_state_held.clients_info[curr_client_id].hand.len() == 0 {
-                                send_packet(&mut shrared_state_held.clients_info[curr_client_id].stream, ServerPacket::YouWon);
-                                shrared_state_held.clients_info[curr_client_id].is_active = false;
-                                match shrared_state_held.is_game_over() {
-                                    Some(id) => {
-                                        bunt::println!("{$yellow}All but one clients are inactive, Game Over!{/$}");
-                                        send_packet(&mut shrared_state_held.clients_info[id].stream, ServerPacket::YouLost);
-                                        shrared_state_held.game_phase = GamePhase::GameOver;
-                                        return;
-                                    }
-                                    None => {},
-                                }
-                                // shrared_state_held.clients_info.remove(curr_client_id);
-                            }
-                        }
-                        Err(e) => {
-                            send_packet(&mut shrared_state_held.clients_info[curr_client_id].stream, ServerPacket::SendMoveAcknowledgement { msg: Some(e) });
-                        }
-                    }
-                }
-                ClientPacket::SendMovePick => {
-                    let pick_up_amt = if shrared_state_held.card_debt > 0 {shrared_state_held.card_debt} else {1};
-                    for _ in 0..pick_up_amt {
-                        let card = shrared_state_held.master_deck.pop_random_card();
-                        shrared_state_held.clients_info[curr_client_id].hand.push(card);
-                    }
-                    shrared_state_held.next_player();
-                    shrared_state_held.card_debt = 0;
-                }
-                _ => server_received_unexpected_packet!(),
-            }
-        }
-    }
+    let listener = TcpListener::bind(format!("localhost:{}", config.port))?;
+    // The deck, hands and the first card are dealt once a game starts, see Room::deal
+    let shared_server_data = Arc::new(Mutex::new(ServerData::new(config)));
 
     /*
-     * server commands thread: Executes commands sent to the server.
+     * Game thread: every client has a thread reading its packets, which all end up here and are
+     * handled one at a time. The state is only locked while a packet is handled, so chat keeps
+     * flowing and the console keeps working while players think. In between, the turn timers
+     * are run.
      */
-    tokio::spawn({
-        let shared_state = shared_global_game_data.clone();
-        // let tx = tx.clone();
-        async move {
-            let mut input_line;
-            let possible_commands = vec!["start", "clients_info"].iter().map(|elem| elem.to_string()).collect::<Vec<String>>();
-            loop {
-                input_line = String::new();
-                std::io::stdin().read_line(&mut input_line).unwrap();
-                input_line = input_line.trim().to_lowercase();
-                if !possible_commands.contains(&input_line) {
-                    bunt::println!("{$red}Unrecongized command. Valid commnads are: {:?} {/$}", possible_commands);
-                }
-                else if input_line == "clients_info" {
-                    let shared_state_locked = shared_state.lock().unwrap();
-                    dbg!(&shared_state_locked.clients_info);
-                }
-                else if input_line == "start" {
-                    if shared_state.lock().unwrap().game_phase != GamePhase::Waiting {
-                        bunt::println!("{$red}Game cannot be started if it already has started{/$}");
-                    }
-                    else if shared_state.lock().unwrap().curr_total_clients_num < 2 {
-                        bunt::println!("{$red}Game cannot be started if number of players less than 2{/$}");
-                    }
-                    else {
-                        // tx.send(GameThreadBroadcastPacket::StartGame).unwrap();
-                        bunt::println!("{$magenta}Game Started!{/$}");
-                        // FIXME: Can possibly run this async (without await). To do that, need to
-                        // not hold mutex throughout the lifetime of game_thread()...
-                        tokio::spawn(game_thread(shared_state.clone())).await.unwrap();
-                        if shared_state.lock().unwrap().game_phase == GamePhase::GameOver {
-                            bunt::println!("{$yellow}Game has ended. Thanks for playing! :){/$}");
-                            return Ok::<(), ()>(())
-                        }
-                    }
-                }
+    let (tx, rx) = mpsc::channel::<Incoming>();
+    thread::spawn({
+        let shared_state = shared_server_data.clone();
+        move || loop {
+            match rx.recv_timeout(TICK) {
+                Ok(incoming) => shared_state.lock().unwrap().handle(incoming),
+                Err(mpsc::RecvTimeoutError::Timeout) => {},
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
             }
+            shared_state.lock().unwrap().tick(Instant::now());
         }
     });
 
+    /*
+     * server commands thread: Executes commands typed on the server, see console.rs
+     */
+    thread::spawn({
+        let shared_state = shared_server_data.clone();
+        let tx = tx.clone();
+        move || console::run(shared_state, tx)
+    });
+
     /* 
      * Client serving server thread: A server thread (per client) to serve the client. All these
      * threads "talk" with the 
      */
     loop {
         let (mut stream, _) = listener.accept()?;
-        let shared_state = shared_global_game_data.clone();
+        if let Err(e) = stream.set_write_timeout(Some(WRITE_TIMEOUT)) {
+            bunt::println!("{$red}A client failed to join: {}{/$}", e);
+            continue;
+        }
+        let shared_state = shared_server_data.clone();
+        let tx = tx.clone();
 
         // for every new connection
         tokio::spawn(async move {
@@ -346,9 +280,9 @@ pub async fn run_server(port : u32) -> Result<(), Box<dyn std::error::Error>> {
         // ==== Setting Client Name ====
         send_packet(&mut stream, ServerPacket::AskPreferredName);
         // Wait for client to send thier name
-        let mut ret_name = "Unnamed".to_string();
         match read_packet::<ClientPacket>(&mut stream) {
-            ClientPacket::SendPreferredName { optional_client_name} => {
+            Ok(ClientPacket::SendPreferredName { optional_client_name }) => {
+                let ret_name;
                 let ret_msg;
                 match optional_client_name {
                     Some(name) => {
@@ -357,140 +291,420 @@ pub async fn run_server(port : u32) -> Result<(), Box<dyn std::error::Error>> {
                             Ok(name) => {ret_name = name; ret_msg = None;}
                             Err(()) => {
                                 ret_name = shared_state.lock().unwrap().names.get_random_name();
-                                ret_msg = Some("Provided name was invalid, hence random name assigned. Names must not have whitespaces and the '#' char. You may reconnect with a valid name.".to_string());
+                                ret_msg = Some(format!("Provided name was invalid, hence random name assigned. Names must be at most {} characters long \
+                                                        and must not have whitespaces and the '#' char. You may reconnect with a valid name.", MAX_NAME_LEN));
                             }
                         }
                     }
                     None => {ret_name = shared_state.lock().unwrap().names.get_random_name();ret_msg = None}
                 }
                 send_packet(&mut stream, ServerPacket::SendGivenName { name: ret_name.clone(), optional_msg: ret_msg });
-                {
-                    let mut locked_game_data = shared_state.lock().unwrap();
-                    locked_game_data.curr_total_clients_num += 1;
-                    let hand = Hand::new(7, &mut locked_game_data.master_deck); //TODO: let users
-                                                                                //decide how many
-                                                                                //cards to start
-                                                                                //with
-                    locked_game_data.clients_info.push(ClientInfo {
-                        name: ret_name.clone(), hand, stream, is_active: true,
-                    })
+                // Banned players are turned away where a room would be joined, which the client expects
+                if shared_state.lock().unwrap().is_banned(&ret_name) {
+                    bunt::println!("{$yellow}{} is banned and was turned away{/$}", ret_name);
+                    send_packet(&mut stream, ServerPacket::SendMsg { msg: Some("You are banned from this server.".to_string()) });
+                    return;
                 }
+                let mut reader = match stream.try_clone() {
+                    Ok(reader) => reader,
+                    Err(e) => {bunt::println!("{$red}A client failed to join: {}{/$}", e); return;}
+                };
+                // Rooms are picked from the lobby, see ServerData::handle_lobby
+                let client_id = {
+                    let mut locked_server_data = shared_state.lock().unwrap();
+                    let client_id = next_client_id();
+                    let ping_interval = locked_server_data.config.ping_interval;
+                    locked_server_data.lobby.push(ClientInfo::new(client_id, stream, ret_name.clone(), ping_interval));
+                    client_id
+                };
+                bunt::println!("{$green}{} has connected!{/$}", ret_name);
+                thread::spawn(move || loop {
+                    let packet = read_packet::<ClientPacket>(&mut reader);
+                    let failed = packet.is_err();
+                    if tx.send(Incoming { client_id, packet }).is_err() || failed {
+                        break;
+                    }
+                });
             }
-            _ => server_received_unexpected_packet!(),
+            Ok(_) => server_received_unexpected_packet!(),
+            Err(e) => bunt::println!("{$red}A client failed to join: {}{/$}", e),
         }
-        bunt::println!("{$green}{} has joined the game!{/$}", ret_name);
         // At this point, the client has connected to the server!
         });
     }
 }
 
-pub async fn run_client(optional_client_name : Option<&String>, join_code_: String) -> Result<(), Box<dyn std::error::Error>> {
+/// The players list, the side in play (Uno Flip), the topmost card and what is owed
+fn table_string(snapshot: &GameSnapshot) -> String {
+    let mut ret_string = "\nPlayers: \n".to_string();
+    for (idx, player) in snapshot.players.iter().enumerate() {
+        ret_string += if idx == snapshot.turn {"* "} else {"  "};
+        ret_string += &format!("{}. ({}) {}", idx + 1, player.num_cards, player.name);
+        if let Some(latency_ms) = player.latency_ms {
+            ret_string += &format!(" [{}ms]", latency_ms);
+        }
+        if Some(idx) == snapshot.you {
+            ret_string += " (you)";
+        }
+        if player.is_eliminated {
+            ret_string += " [out]";
+        }
+        else if !player.is_active {
+            ret_string += " [done]";
+        }
+        ret_string += "\n";
+    }
+    ret_string += &format!("Turns go {} the list\n\n", if snapshot.direction == Direction::Positive {"down"} else {"up"});
+    if snapshot.rules == RulesKind::Flip {
+        ret_string += &format!("Side in play: {}\n", if snapshot.table.dark_side {"Dark"} else {"Light"});
+    }
+    ret_string += &format!("Topmost card: {}\n", snapshot.table.top_card.get_colorized_repr());
+    if snapshot.table.card_debt > 0 {
+        ret_string += &format!("Cards to pick up: {}\n", snapshot.table.card_debt);
+    }
+    ret_string
+}
+
+/// How many chat messages the line client keeps on screen
+const CHAT_LINES : usize = 6;
+
+/// What the line client waits for
+enum LineEvent {
+    Packet(io::Result<ServerPacket>),
+    Input(String),
+}
+
+/// What the line client needs to know while the player types their move
+struct PendingTurn {
+    hand: Hand,
+    table: TableView,
+    rule_set: Box<dyn RuleSet>,
+    instructions: String,
+}
+
+/// The latest chat messages, printed again after the screen is cleared
+fn print_chat(chat: &VecDeque<String>) {
+    if chat.is_empty() {
+        return;
+    }
+    bunt::println!("{$cyan}---- Chat (type /say <message>) ----{/$}");
+    for line in chat {
+        bunt::println!("{$cyan}{}{/$}", line);
+    }
+}
+
+/// Settings of the client
+pub struct ClientConfig {
+    pub hand_sort: HandSort,
+    /// Pick up right away when no card in the hand can be played
+    pub auto_pick: bool,
+    /// Use the full-screen client (see tui.rs)
+    pub tui: bool,
+    /// Draw the hand as cards (see card_art.rs)
+    pub card_art: bool,
+    /// Watch the game rather than play
+    pub spectator: bool,
+    /// The room to join, or to create when `create_room` is set
+    pub room: String,
+    /// Create the room with these rules instead of joining an existing one
+    pub create_room: Option<RulesKind>,
+    /// Only print the rooms of the server and quit
+    pub list_rooms: bool,
+}
+
+/// The hand as card art if asked for and the terminal is wide enough, as a list otherwise
+fn hand_string<P: Fn(&Card) -> bool>(hand: &Hand, is_marked: P, config: &ClientConfig) -> String {
+    let art = match config.card_art {
+        true => card_art::render_hand(hand, &is_marked, card_art::terminal_width(), ColorDepth::detect(), &Theme::current()),
+        false => None,
+    };
+    art.unwrap_or_else(|| hand.to_string_marking(is_marked))
+}
+
+pub async fn run_client(optional_client_name : Option<&String>, join_code_: String, config: ClientConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let mut hand_sort = config.hand_sort;
     let join_code_pair = join_code_.split_at(1);
     let addr = format!("{}.tcp.ngrok.io:{}", join_code_pair.0, join_code_pair.1);
     let mut stream = TcpStream::connect(addr)?;
-    match read_packet::<ServerPacket>(&mut stream) {
+    match read_packet::<ServerPacket>(&mut stream)? {
         ServerPacket::AskPreferredName => {
             send_packet(&mut stream, ClientPacket::SendPreferredName { optional_client_name: optional_client_name.cloned() })
         }
         _ => client_received_unexpected_packet!()
     }
 
-    match read_packet::<ServerPacket>(&mut stream) {
+    let mut given_name = String::new();
+    match read_packet::<ServerPacket>(&mut stream)? {
         ServerPacket::SendGivenName { name, optional_msg } => {
-            match optional_msg {
-                Some(msg) => {bunt::println!("{$red}{}{/$}", msg)}
-                None => (),
+            if let Some(msg) = optional_msg {
+                bunt::println!("{$red}{}{/$}", msg)
             }
-            bunt::println!("{$green}Your name is: {}{/$}", name)
+            bunt::println!("{$green}Your name is: {}{/$}", name);
+            given_name = name;
+        }
+        _ => client_received_unexpected_packet!()
+    }
+
+    // ==== Picking a room in the lobby ====
+    if config.list_rooms {
+        send_packet(&mut stream, ClientPacket::ListRooms);
+        match read_packet::<ServerPacket>(&mut stream)? {
+            ServerPacket::SendRoomList { rooms } => rooms.iter().for_each(|room| println!("{}", room)),
+            _ => client_received_unexpected_packet!()
+        }
+        return Ok(());
+    }
+    match config.create_room {
+        Some(rules) => send_packet(&mut stream, ClientPacket::CreateRoom { room: config.room.clone(), rules }),
+        None => send_packet(&mut stream, ClientPacket::JoinRoom { room: config.room.clone(), spectator: config.spectator }),
+    }
+    match read_packet::<ServerPacket>(&mut stream)? {
+        ServerPacket::RoomJoined { room } => bunt::println!("{$green}You are in the room {}{/$}", room),
+        ServerPacket::SendMsg { msg: Some(msg) } => {
+            bunt::println!("{$red}{}{/$}", msg);
+            return Ok(());
         }
         _ => client_received_unexpected_packet!()
     }
 
+    if config.tui {
+        return crate::tui::run(stream, given_name, config);
+    }
+
     // At this point, the client has connected to the server!
+    // Packets and typed lines are read by their own threads, so that chat can come in and go out
+    // while waiting for either
+    let (tx, rx) = mpsc::channel::<LineEvent>();
+    let mut reader = stream.try_clone()?;
+    thread::spawn({
+        let tx = tx.clone();
+        move || loop {
+            let packet = read_packet::<ServerPacket>(&mut reader);
+            let failed = packet.is_err();
+            if tx.send(LineEvent::Packet(packet)).is_err() || failed {
+                break;
+            }
+        }
+    });
+    thread::spawn(move || {
+        for line in io::stdin().lines() {
+            let Ok(line) = line else {break};
+            if tx.send(LineEvent::Input(line)).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut chat = VecDeque::new();
+    // Shown under the table to spectators, whose screen is cleared as soon as the table changes
+    let mut recent_msgs = VecDeque::new();
+    let mut turn : Option<PendingTurn> = None;
+    // The turn a move was sent for, given back if the server turns the move down
+    let mut sent_turn : Option<PendingTurn> = None;
     cls!();
-    loop {
-        let packet = read_packet::<ServerPacket>(&mut stream);
-        match packet {
-            ServerPacket::SendMsgUpdate { msg_first_half, hand, msg_second_half, is_my_turn } => {
-                println!("{}", msg_first_half);
-                println!("{}", hand);
-                match is_my_turn {
-                    // FIXME: Kinda rework on this. Implement case for 2 blue etc.
-                    true => {
-                        print!("It is your turn! ");
-                        println!("{}", msg_second_half);
-                        loop {
-                            let mut input_str : String = "".to_string();
-                            io::stdin().read_line(&mut input_str).expect("FATAL ERROR: Could not read line");
-                            let mut input_words = input_str.split_whitespace();
-                            let first_input = input_words.next(); // Must be either a number or 'p'
-                            if first_input.is_none() {bunt::println!("{$red}Invalid Input, try again:{/$}"); continue;}
-                            match first_input.unwrap().trim().parse::<usize>() {
-                                // we were able to parse the first_input as a number and the idx is
-                                // in a valid range
-                                Ok(card_idx) if card_idx > 0 && card_idx <= hand.len() => {
-                                    match hand.get_at(card_idx).kind {
-                                        card::CardKind::Draw4 | card::CardKind::Wild  => {
-                                            let second_input = input_words.next();
-                                            if second_input.is_none() {
-                                                bunt::println!("{$red}Invalid Input. \
-                                                               You must include a color when choosing the Draw4 or wild card, try again:{/$}");
-                                                continue;
-                                            }
-                                            let chosen_color : Color = match second_input.unwrap().chars().next() {
-                                                Some(c) if c.to_ascii_lowercase() == 'r' => Color::Red,
-                                                Some(c) if c.to_ascii_lowercase() == 'g' => Color::Green,
-                                                Some(c) if c.to_ascii_lowercase() == 'b' => Color::Blue,
-                                                Some(c) if c.to_ascii_lowercase() == 'y' => Color::Yellow,
-                                                Some(_) => {bunt::println!("{$red}Invalid Input. Could not parse color choice. Try again:{/$}"); continue;}
-                                                None => {bunt::println!("{$red}Invalid Input, try again:{/$}"); continue;}
-                                            };
-                                            send_packet(&mut stream, ClientPacket::SendMoveCard { card_idx, color_choice: Some(chosen_color) });
-                                            cls!();
-                                            break;
-                                    }
-                                    _ => {
-                                        send_packet(&mut stream, ClientPacket::SendMoveCard { card_idx, color_choice: None});
-                                        cls!();
-                                        break;
-                                    }
-                                }
+    for event in rx {
+        let input_str = match event {
+            LineEvent::Packet(packet) => {
+                match packet? {
+                    // Spectators have no hand and no turn, only the table
+                    ServerPacket::SendGameState { snapshot } if snapshot.you.is_none() => {
+                        cls!();
+                        print_chat(&chat);
+                        println!("{}", table_string(&snapshot));
+                        recent_msgs.iter().for_each(|msg| println!("{}", msg));
+                        println!("You are watching the game. Type '/say <message>' to chat.");
+                    }
+                    ServerPacket::SendGameState { snapshot } => {
+                        let is_my_turn = snapshot.is_my_turn();
+                        turn = None;
+                        sent_turn = None;
+                        println!("{}", table_string(&snapshot));
+                        let GameSnapshot { mut hand, table, rules, .. } = snapshot;
+                        hand.sort(hand_sort);
+                        // Which deck is played with only matters to the server, the client only checks cards
+                        let rule_set = rules.rule_set(rules.default_deck_definition());
+                        let is_playable = |card: &Card| is_my_turn && rule_set.verify_card(&table, &hand, card).is_ok();
+                        let msg_second_half = rule_set.instructions(&table, &hand);
+                        println!("{}", hand_string(&hand, is_playable, &config));
+                        match is_my_turn {
+                            true if config.auto_pick && !hand.iter().any(is_playable) => {
+                                bunt::println!("{$magenta}Nothing to play, picking up.{/$}");
+                                send_packet(&mut stream, ClientPacket::SendMovePick);
+                                cls!();
+                                print_chat(&chat);
                             }
-                                Ok(card_idx) if !(card_idx > 0 && card_idx <= hand.len()) => {bunt::println!("{$red}Invalid Input. Card index not in range! try again:{/$}"); continue;}
-                                _ => {
-                                    if input_str.trim().to_lowercase() == *"p" {
-                                        send_packet(&mut stream, ClientPacket::SendMovePick);
-                                        cls!();
-                                        break;
-                                    }
-                                    // Not a number, not 'p', but also not whitespace
-                                    else {
-                                        bunt::println!("{$red}Invalid Input, try again:{/$}");
-                                        continue;
-                                    }
-                                }
+                            true => {
+                                print!("It is your turn! ");
+                                println!("{}", msg_second_half);
+                                turn = Some(PendingTurn { hand, table, rule_set, instructions: msg_second_half });
                             }
+                            false => {println!("It is not your turn."); cls!(); print_chat(&chat);},
+                        }
+                    }
+                    ServerPacket::SendMoveAcknowledgement { error: Some(error) } => {
+                        bunt::println!("{$red}{}{/$}", error);
+                        // The screen was cleared when the move was sent
+                        if let Some(pending) = sent_turn.take() {
+                            println!("{}", hand_string(&pending.hand, |card| pending.rule_set.verify_card(&pending.table, &pending.hand, card).is_ok(), &config));
+                            println!("{}", pending.instructions);
+                            turn = Some(pending);
+                        }
+                    }
+                    ServerPacket::SendMsg { msg: Some(msg) } => {
+                        println!("{}", msg);
+                        recent_msgs.push_back(msg);
+                        if recent_msgs.len() > CHAT_LINES {
+                            recent_msgs.pop_front();
+                        }
+                    }
+                    ServerPacket::SendChat { from, msg } => {
+                        let line = format!("{}: {}", from, msg);
+                        bunt::println!("{$cyan}[chat] {}{/$}", line);
+                        chat.push_back(line);
+                        if chat.len() > CHAT_LINES {
+                            chat.pop_front();
                         }
                     }
-                    false => {println!("It is not your turn."); cls!();},
+                    ServerPacket::YouWon => bunt::println!("{$yellow}You Won!!{/$}"),
+                    ServerPacket::YouLost => bunt::println!("{$red}You Lost.{/$}"),
+                    ServerPacket::Ping { id } => send_packet(&mut stream, ClientPacket::Pong { id }),
+                    ServerPacket::TurnTimer { seconds } if turn.is_some() => {
+                        bunt::println!("{$yellow}You have {} seconds to move.{/$}", seconds);
+                    }
+                    ServerPacket::GameEnded if config.spectator => {
+                        println!("The game is over. Stay to watch the rematch, or type '/quit' to leave.");
+                    }
+                    ServerPacket::GameEnded => {
+                        turn = None;
+                        println!("The game is over. Type '/rematch' to vote for a rematch, or '/quit' to leave.");
+                    }
+                    _ => {}
                 }
+                continue;
             }
-            ServerPacket::SendMoveAcknowledgement { msg } => {
-                match msg {
-                    Some(msg) => bunt::println!("{$red}{}{/$}", msg),
-                    None => (),
-                }
+            LineEvent::Input(input_str) => input_str,
+        };
+        // Chat works at any time
+        if let Some(msg) = input_str.trim_start().strip_prefix("/say") {
+            match msg.trim().is_empty() {
+                true => bunt::println!("{$red}Type your message after /say{/$}"),
+                false => send_packet(&mut stream, ClientPacket::SendChat { msg: msg.trim().to_string() }),
             }
-            ServerPacket::YouWon => {
-                bunt::println!("{$yellow}You Won!!{/$}");
-                break;
+            continue;
+        }
+        match parse_host_command(&input_str) {
+            Some(Ok(command)) => {
+                send_packet(&mut stream, ClientPacket::HostCommand { command });
+                continue;
             }
-            ServerPacket::YouLost => {
-                bunt::println!("{$red}You Lost.{/$}");
-                break;
+            Some(Err(e)) => {
+                bunt::println!("{$red}{}{/$}", e);
+                continue;
+            }
+            None => {}
+        }
+        match input_str.trim() {
+            "/quit" => break,
+            "/rematch" => {
+                send_packet(&mut stream, ClientPacket::VoteRematch);
+                continue;
             }
             _ => {}
         }
+        let Some(PendingTurn { hand, table, rule_set, instructions }) = turn.as_mut() else {
+            match config.spectator {
+                true => println!("You are watching the game. Type '/say <message>' to chat."),
+                false => println!("It is not your turn. Type '/say <message>' to chat."),
+            }
+            continue;
+        };
+        // Only the order on screen changes, moves are sent by card id
+        if input_str.trim().to_lowercase() == *"s" {
+            hand_sort = hand_sort.next();
+            hand.sort(hand_sort);
+            bunt::println!("{$magenta}Sorted by: {:?}{/$}", hand_sort);
+            println!("{}", hand_string(hand, |card| rule_set.verify_card(table, hand, card).is_ok(), &config));
+            println!("{}", instructions);
+            continue;
+        }
+        match parse_move(&input_str, hand) {
+            Ok(packet) => {
+                send_packet(&mut stream, packet);
+                sent_turn = turn.take();
+                cls!();
+                print_chat(&chat);
+            }
+            Err(e) => bunt::println!("{$red}{}{/$}", e),
+        }
     }
     Ok(())
 }
+
+/// Turns what the player typed on their turn into a move: the number of a card, followed by a
+/// color for wilds or a player number for cards played on someone, or 'p' to pick up
+fn parse_move(input_str: &str, hand: &Hand) -> Result<ClientPacket, &'static str> {
+    let mut input_words = input_str.split_whitespace();
+    let Some(first_input) = input_words.next() else {return Err("Invalid Input, try again:")};
+    match first_input.parse::<usize>() {
+        Ok(card_idx) if card_idx > 0 && card_idx <= hand.len() => {
+            let card = hand.get_at(card_idx);
+            if card.kind.is_wild() {
+                let Some(second_input) = input_words.next() else {
+                    return Err("Invalid Input. You must include a color when choosing a wild card, try again:");
+                };
+                let Some(chosen_color) = Color::from_input(second_input) else {
+                    return Err("Invalid Input. Could not parse color choice. Try again:");
+                };
+                return Ok(ClientPacket::SendMoveCard { card_id: card.id, color_choice: Some(chosen_color), target_player: None });
+            }
+            // A player number can follow the card, eg: for a 7 in No Mercy
+            let target_player = match input_words.next().map(|word| word.parse::<usize>()) {
+                Some(Ok(target_player)) => Some(target_player),
+                Some(Err(_)) => return Err("Invalid Input. Could not parse player number. Try again:"),
+                None => None,
+            };
+            Ok(ClientPacket::SendMoveCard { card_id: card.id, color_choice: None, target_player })
+        }
+        Ok(_) => Err("Invalid Input. Card index not in range! try again:"),
+        Err(_) if input_str.trim().to_lowercase() == *"p" => Ok(ClientPacket::SendMovePick),
+        // Not a number, not 'p', but also not whitespace
+        Err(_) => Err("Invalid Input, try again:"),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::bot::Difficulty;
+
+    /// A server with the default settings, that isn't listening
+    pub fn test_server() -> ServerData {
+        ServerData::new(ServerConfig {
+            port: 0, num_decks: None, deck_definition: DeckDefinition::standard(), rules: RulesKind::House, rotate_first: false, turn_time: None,
+            stand_in: false, ping_interval: Duration::from_secs(5), max_missed_pongs: 3, bot_budget: Duration::from_millis(100),
+            bot_time_limit: Duration::from_secs(10),
+        })
+    }
+
+    #[test]
+    fn rooms_left_during_a_game_close() {
+        let mut server = test_server();
+        let settings = server.config.room_settings();
+        let mut room = Room::new("blue_table".to_string(), server.config.rules.rule_set(DeckDefinition::standard()), settings);
+        room.add_bot(Difficulty::Easy).unwrap();
+        room.add_bot(Difficulty::Hard).unwrap();
+        room.start_game();
+        assert_eq!(room.game_phase, GamePhase::InGame);
+        server.rooms.push(room);
+        server.room_mut(DEFAULT_ROOM).unwrap().add_bot(Difficulty::Easy).unwrap();
+        server.room_mut(DEFAULT_ROOM).unwrap().add_bot(Difficulty::Easy).unwrap();
+        server.room_mut(DEFAULT_ROOM).unwrap().start_game();
+        server.clean_up_rooms();
+        assert!(server.room_mut("blue_table").is_none());
+        // The default room is set up again, waiting for players
+        assert_eq!(server.rooms.len(), 1);
+        assert_eq!(server.room_mut(DEFAULT_ROOM).unwrap().game_phase, GamePhase::Waiting);
+        server.clean_up_rooms();
+        assert_eq!(server.rooms.len(), 1);
+    }
+}
@@ -0,0 +1,250 @@
+/* Server console
+ * - Commands typed on the server's terminal, with history (up and down arrows) and tab
+ * completion of commands, rooms, players and rules.
+ * - Commands about a room act on the default room unless another one is named last, eg:
+ * `start blue_table`. `spawn` takes the room first, as the command it runs goes on to the end.
+ */
+
+use std::sync::{Arc, Mutex, mpsc::Sender};
+
+use clap::ValueEnum;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use serde::{Serialize, Deserialize};
+
+use crate::bot::Difficulty;
+use crate::netcode::client_server::{ServerData, DEFAULT_ROOM};
+use crate::netcode::packets::ServerPacket;
+use crate::netcode::room::{Incoming, Room, SavedRoom};
+use crate::rules::RulesKind;
+
+/// Where `save` writes to when no file is given
+const DEFAULT_SAVE_FILE : &str = "runo-save.json";
+
+/// Every command, with its arguments and what it does, as shown by `help`
+const COMMANDS : [(&str, &str, &str); 16] = [
+    ("help", "", "Show this list"),
+    ("rooms", "", "List the rooms and the lobby"),
+    ("status", "[ROOM]", "Show the players of a room, their cards and who is playing"),
+    ("start", "[ROOM]", "Start the game"),
+    ("pause", "[ROOM]", "Pause the game, moves are turned down until it is resumed"),
+    ("resume", "[ROOM]", "Resume a paused game"),
+    ("skip", "[ROOM]", "Make the player whose turn it is pick up, for when they are stuck"),
+    ("end", "[ROOM]", "End the game and open the rematch vote"),
+    ("rules", "<RULES> [ROOM]", "Change the rules between games"),
+    ("bot", "<LEVEL> [ROOM]", "Seat a bot (easy, normal, hard or expert) between games, `kick` it to remove it"),
+    ("spawn", "<ROOM> <COMMAND>", "Start a program that plays as a bot, see the README for how it talks to the server"),
+    ("kick", "<NAME>", "Disconnect a player or spectator"),
+    ("ban", "<NAME>", "Kick someone and turn the name away from now on"),
+    ("unban", "<NAME>", "Let a banned name join again"),
+    ("say", "<MESSAGE>", "Send a message to everyone"),
+    ("save", "[FILE]", "Write the rooms and their games to a JSON file"),
+];
+
+/// What `save` writes
+#[derive(Serialize, Deserialize)]
+pub struct SavedServer {
+    pub rooms: Vec<SavedRoom>,
+    pub banned: Vec<String>,
+}
+
+/// Completes the word under the cursor from the server's current rooms and players
+struct ConsoleHelper {
+    shared_state: Arc<Mutex<ServerData>>,
+}
+
+impl Completer for ConsoleHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos].rfind(' ').map_or(0, |idx| idx + 1);
+        let word = &line[start..pos];
+        let command = line.split_whitespace().next().unwrap_or("");
+        let options = match start {
+            0 => COMMANDS.iter().map(|(command, _, _)| command.to_string()).collect(),
+            _ if command == "rules" && line[..start].split_whitespace().count() == 1 => {
+                RulesKind::value_variants().iter().filter_map(|rules| rules.to_possible_value()).map(|value| value.get_name().to_string()).collect()
+            }
+            _ if command == "bot" && line[..start].split_whitespace().count() == 1 => {
+                Difficulty::value_variants().iter().filter_map(|level| level.to_possible_value()).map(|value| value.get_name().to_string()).collect()
+            }
+            _ => {
+                let server = self.shared_state.lock().unwrap();
+                let rooms = server.rooms.iter().map(|room| room.name.clone());
+                let clients = server.rooms.iter().flat_map(|room| room.clients_info.iter().chain(room.spectators.iter()))
+                    .chain(server.lobby.iter())
+                    .map(|client| client.name.clone());
+                rooms.chain(clients).collect::<Vec<String>>()
+            }
+        };
+        Ok((start, options.into_iter().filter(|option| option.starts_with(word)).collect()))
+    }
+}
+
+impl Hinter for ConsoleHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ConsoleHelper {}
+impl Validator for ConsoleHelper {}
+impl Helper for ConsoleHelper {}
+
+/// Reads and runs commands until the console is closed. `tx` is where external bots send their
+/// moves.
+pub fn run(shared_state: Arc<Mutex<ServerData>>, tx: Sender<Incoming>) {
+    let mut editor = match Editor::<ConsoleHelper, DefaultHistory>::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            bunt::println!("{$red}The console could not be opened: {}{/$}", e);
+            return;
+        }
+    };
+    editor.set_helper(Some(ConsoleHelper { shared_state: shared_state.clone() }));
+    loop {
+        match editor.readline("") {
+            Ok(line) => {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let _ = editor.add_history_entry(line.as_str());
+                let mut server = shared_state.lock().unwrap();
+                if let Err(e) = run_command(&mut server, &line, &tx) {
+                    bunt::println!("{$red}{}{/$}", e);
+                }
+                server.clean_up_rooms();
+            }
+            // Ctrl+C only clears the line, so the server isn't stopped by mistake
+            Err(ReadlineError::Interrupted) => continue,
+            // Without a console (eg: run headless), the hosts of the rooms are in charge
+            Err(_) => break,
+        }
+    }
+}
+
+/// The room named by the last argument, if any, or the default room
+fn room<'a>(server: &'a mut ServerData, room_name: Option<&str>) -> Result<&'a mut Room, String> {
+    let room_name = room_name.unwrap_or(DEFAULT_ROOM);
+    server.room_mut(room_name).ok_or(format!("There is no room called {}", room_name))
+}
+
+fn run_command(server: &mut ServerData, line: &str, tx: &Sender<Incoming>) -> Result<(), String> {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or("").to_lowercase();
+    let argument = words.next();
+    // Names can have spaces in them (eg: Red Penguin), so they go on to the end of the line
+    let rest = line.trim_start()[command.len()..].trim();
+    match (command.as_str(), argument) {
+        ("help", _) => {
+            for (command, args, help) in COMMANDS {
+                println!("  {:<8} {:<16} {}", command, args, help);
+            }
+        }
+        ("rooms", _) => {
+            for room in server.rooms.iter() {
+                println!("{}", room.summary());
+            }
+            let lobby = server.lobby.iter().map(|client| client.name.as_str()).collect::<Vec<&str>>();
+            println!("{} client(s) in the lobby {}", lobby.len(), lobby.join(", "));
+        }
+        ("status", room_name) => print!("{}", room(server, room_name)?.status_table()),
+        ("start", room_name) => {
+            let room = room(server, room_name)?;
+            room.check_can_start()?;
+            room.start_game();
+        }
+        ("pause", room_name) => room(server, room_name)?.set_paused(true)?,
+        ("resume", room_name) => room(server, room_name)?.set_paused(false)?,
+        ("skip", room_name) => room(server, room_name)?.skip_turn()?,
+        ("end", room_name) => room(server, room_name)?.end_game()?,
+        ("rules", Some(rules)) => {
+            let rules = RulesKind::from_str(rules, true).map_err(|_| format!("Unknown rules {}", rules))?;
+            room(server, words.next())?.set_rules(rules)?;
+        }
+        ("bot", Some(level)) => {
+            let difficulty = Difficulty::from_str(level, true).map_err(|_| format!("Unknown difficulty {}", level))?;
+            room(server, words.next())?.add_bot(difficulty)?;
+        }
+        ("spawn", Some(room_name)) => {
+            let command = words.collect::<Vec<&str>>();
+            room(server, Some(room_name))?.add_external(&command, tx.clone())?;
+        }
+        ("kick", Some(_)) => kick(server, rest)?,
+        ("ban", Some(_)) => {
+            let name = rest;
+            server.banned.push(name.split('#').next().unwrap_or(name).to_string());
+            // Someone who isn't connected right now can be banned too
+            let _ = kick(server, name);
+            bunt::println!("{$yellow}{} is banned{/$}", name);
+        }
+        ("unban", Some(_)) => {
+            let name = rest;
+            let num_banned = server.banned.len();
+            server.banned.retain(|banned| banned != name);
+            if server.banned.len() == num_banned {
+                return Err(format!("{} is not banned", name));
+            }
+        }
+        ("say", Some(_)) => {
+            let msg = rest;
+            server.rooms.iter_mut().for_each(|room| room.broadcast(msg));
+            for client in server.lobby.iter_mut() {
+                client.send(ServerPacket::SendChat { from: "[server]".to_string(), msg: msg.to_string() });
+            }
+        }
+        ("save", file) => {
+            let file = file.unwrap_or(DEFAULT_SAVE_FILE);
+            let saved = SavedServer { rooms: server.rooms.iter().map(Room::save).collect(), banned: server.banned.clone() };
+            let json = serde_json::to_string_pretty(&saved).map_err(|e| e.to_string())?;
+            std::fs::write(file, json).map_err(|e| format!("Could not write {}: {}", file, e))?;
+            bunt::println!("{$green}Saved to {}{/$}", file);
+        }
+        (command, None) if COMMANDS.iter().any(|(other, _, _)| *other == command) => {
+            return Err(format!("{} needs an argument, type `help` to see how it is used", command));
+        }
+        _ => return Err("Unrecognized command, type `help` to see the commands".to_string()),
+    }
+    Ok(())
+}
+
+/// Kicks someone from whichever room they are in, or from the lobby
+fn kick(server: &mut ServerData, name: &str) -> Result<(), String> {
+    if let Some(room) = server.rooms.iter_mut().find(|room| room.clients_info.iter().chain(room.spectators.iter()).any(|client| client.name == name && client.connected)) {
+        return room.kick(name);
+    }
+    let Some(idx) = server.lobby.iter().position(|client| client.name == name) else {
+        return Err(format!("There is no player called {}", name));
+    };
+    let mut client = server.lobby.remove(idx);
+    client.send(ServerPacket::SendMsg { msg: Some("You were kicked from the server.".to_string()) });
+    client.disconnect();
+    bunt::println!("{$yellow}{} was kicked{/$}", name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use crate::netcode::client_server::tests::test_server;
+    use crate::netcode::room::ClientInfo;
+
+    #[test]
+    fn names_with_spaces() {
+        let mut server = test_server();
+        let (tx, _rx) = mpsc::channel();
+        server.lobby.push(ClientInfo::new_bot("Red Penguin".to_string(), Difficulty::Easy));
+        assert!(run_command(&mut server, "kick Red Penguin", &tx).is_ok());
+        assert!(server.lobby.is_empty());
+        server.lobby.push(ClientInfo::new_bot("Blue Deer".to_string(), Difficulty::Easy));
+        assert!(run_command(&mut server, "ban Blue Deer", &tx).is_ok());
+        assert!(server.lobby.is_empty());
+        assert!(server.is_banned("Blue Deer#2"));
+        assert!(run_command(&mut server, "unban Blue Deer", &tx).is_ok());
+        assert!(!server.is_banned("Blue Deer"));
+    }
+}
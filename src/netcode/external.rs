@@ -0,0 +1,95 @@
+/* External bots
+ * - A program started by the server plays in a seat. It is sent every ServerPacket as a line of
+ * JSON on its stdin, and answers with ClientPackets, one line of JSON each on its stdout, eg:
+ * `{"SendMoveCard":{"card_id":12,"color_choice":null,"target_player":null}}` or `"SendMovePick"`.
+ * - Its stdout is read by a thread of its own, like the stream of a client, so that the game
+ * thread handles its moves like anyone else's. What it writes to stderr shows on the server.
+ * - Its stdin is written by another thread, so that a program that stops reading never blocks
+ * the server. It is disconnected once OUTGOING_LINES packets are waiting to be written.
+ */
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Sender, SyncSender, TrySendError};
+use std::thread;
+
+use crate::netcode::packets::{ClientPacket, ServerPacket};
+use crate::netcode::room::{ClientId, Incoming};
+
+/// Packets that can wait for the program to read them
+const OUTGOING_LINES : usize = 256;
+
+#[derive(Debug)]
+pub struct ExternalBot {
+    child: Child,
+    outgoing: SyncSender<String>,
+}
+
+impl ExternalBot {
+    /// Starts `command` (the program and its arguments) and passes whatever it sends on to `tx`
+    pub fn spawn(command: &[&str], client_id: ClientId, tx: Sender<Incoming>) -> io::Result<ExternalBot> {
+        let (program, args) = command.split_first().ok_or(io::Error::new(io::ErrorKind::InvalidInput, "No program to run"))?;
+        let mut child = Command::new(program).args(args).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::inherit()).spawn()?;
+        let mut stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let packet = match line {
+                    Ok(line) if line.trim().is_empty() => continue,
+                    Ok(line) => serde_json::from_str::<ClientPacket>(&line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+                    Err(e) => Err(e),
+                };
+                let failed = packet.is_err();
+                if tx.send(Incoming { client_id, packet }).is_err() || failed {
+                    return;
+                }
+            }
+            let _ = tx.send(Incoming { client_id, packet: Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the program stopped")) });
+        });
+        let (outgoing, lines) = mpsc::sync_channel::<String>(OUTGOING_LINES);
+        thread::spawn(move || {
+            for line in lines {
+                if writeln!(stdin, "{}", line).and_then(|()| stdin.flush()).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(ExternalBot { child, outgoing })
+    }
+
+    /// Fails if the program has fallen too far behind on reading, or stopped reading at all
+    pub fn send(&mut self, packet: &ServerPacket) -> io::Result<()> {
+        let line = serde_json::to_string(packet).unwrap();
+        match self.outgoing.try_send(line) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => Err(io::Error::new(io::ErrorKind::TimedOut, "the program is not reading its input")),
+            Err(TrySendError::Disconnected(_)) => Err(io::Error::new(io::ErrorKind::BrokenPipe, "the program stopped reading its input")),
+        }
+    }
+
+    pub fn kill(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+/// The program doesn't outlive its seat
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        self.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::Color;
+
+    #[test]
+    fn moves_as_json() {
+        let packet = serde_json::from_str::<ClientPacket>(r#"{"SendMoveCard":{"card_id":12,"color_choice":"Red","target_player":null}}"#).unwrap();
+        assert!(matches!(packet, ClientPacket::SendMoveCard { card_id: 12, color_choice: Some(Color::Red), target_player: None }));
+        assert!(matches!(serde_json::from_str::<ClientPacket>(r#""SendMovePick""#).unwrap(), ClientPacket::SendMovePick));
+        assert_eq!(serde_json::to_string(&ServerPacket::YouWon).unwrap(), r#""YouWon""#);
+    }
+}
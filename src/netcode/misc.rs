@@ -1,5 +1,18 @@
-use std::{vec, collections::HashMap};
+use std::{vec, collections::{HashMap, VecDeque}, time::{Duration, Instant}};
 use rand::{thread_rng, seq::SliceRandom};
+use clap::ValueEnum;
+
+use crate::bot::Difficulty;
+use crate::netcode::packets::HostCommand;
+use crate::rules::RulesKind;
+
+/// Longest name (in characters) a client may ask for
+pub const MAX_NAME_LEN : usize = 24;
+/// Longest chat message (in characters), longer ones are cut
+pub const MAX_CHAT_LEN : usize = 200;
+/// A client may send this many chat messages per CHAT_WINDOW
+const CHAT_RATE : usize = 5;
+const CHAT_WINDOW : Duration = Duration::from_secs(10);
 
 #[derive(Debug)]
 pub struct Names {
@@ -9,17 +22,20 @@ pub struct Names {
 
 impl Names {
     pub fn new() -> Self {
-        let colors = vec!["Red", "Blue", "Yellow", "Green", "Purple", "Cyan", "Magenta", "Pink"];
-        let animals = vec!["Penguin", "Deer", "Ostrich", "Giraffe", "Elephant", "Dolphin", "Cat"];
+        Self { possible_generated_names: Self::generate_names(), taken_names:HashMap::new() }
+    }
+
+    fn generate_names() -> Vec<String> {
+        let colors = ["Red", "Blue", "Yellow", "Green", "Purple", "Cyan", "Magenta", "Pink"];
+        let animals = ["Penguin", "Deer", "Ostrich", "Giraffe", "Elephant", "Dolphin", "Cat"];
         let mut possible_generated_names : Vec<String> = vec![];
         for color in colors {
             for animal in &animals {
                 possible_generated_names.push(format!("{color} {animal}"));
-                possible_generated_names.shuffle(&mut thread_rng())
             }
         }
-
-        Self { possible_generated_names, taken_names:HashMap::new() }
+        possible_generated_names.shuffle(&mut thread_rng());
+        possible_generated_names
     }
 
     // Returns the given name if the name hasn't been taken, otherwise returns a 'variant' of the name
@@ -39,14 +55,17 @@ impl Names {
     }
 
     pub fn get_random_name(&mut self) -> String {
-        // TODO: This sets a bound to the number of players who can play this game
-        let mut name = self.possible_generated_names.pop().unwrap(); 
-        name = self.validate_and_register_name(name);
-        name
+        // Once every combination is handed out, start over; the new round of names gets
+        // numbered variants (eg: Red Cat#2) so there is no bound on the number of players
+        if self.possible_generated_names.is_empty() {
+            self.possible_generated_names = Self::generate_names();
+        }
+        let name = self.possible_generated_names.pop().unwrap();
+        self.validate_and_register_name(name)
     }
 
     pub fn get_specific_name(&mut self, name : String) -> Result<String, ()> {
-        if name.contains('#') {
+        if !is_valid_name(&name) {
             return Err(());
         }
         let ret_name = self.validate_and_register_name(name);
@@ -54,6 +73,117 @@ impl Names {
     }
 }
 
+/// Player and room names: not empty, at most MAX_NAME_LEN characters, no whitespace and no '#'
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().count() <= MAX_NAME_LEN
+        && !name.chars().any(|c| c == '#' || c.is_whitespace() || c.is_control())
+}
+
+/// Keeps a client from flooding the chat
+#[derive(Debug, Default)]
+pub struct ChatLimiter {
+    sent: VecDeque<Instant>,
+}
+
+impl ChatLimiter {
+    /// Whether a message sent at `now` goes through. Only messages that went through count.
+    pub fn allow(&mut self, now: Instant) -> bool {
+        while self.sent.front().is_some_and(|&sent| now.duration_since(sent) >= CHAT_WINDOW) {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= CHAT_RATE {
+            return false;
+        }
+        self.sent.push_back(now);
+        true
+    }
+}
+
+/// Keeps a connection in check: pings are sent every so often, and a client that leaves too
+/// many of them unanswered is taken for gone
+#[derive(Debug)]
+pub struct Heartbeat {
+    next_ping: Instant,
+    /// Id of the unanswered ping and when it was sent
+    waiting_for: Option<(u64, Instant)>,
+    last_id: u64,
+    missed_pongs: usize,
+    /// Round trip time of the last answered ping
+    pub latency: Option<Duration>,
+}
+
+impl Heartbeat {
+    pub fn new(now: Instant, interval: Duration) -> Heartbeat {
+        Heartbeat { next_ping: now + interval, waiting_for: None, last_id: 0, missed_pongs: 0, latency: None }
+    }
+
+    /// The id of the ping to send now, if one is due. A ping still unanswered by then is missed.
+    pub fn ping_due(&mut self, now: Instant, interval: Duration) -> Option<u64> {
+        if now < self.next_ping {
+            return None;
+        }
+        if self.waiting_for.is_some() {
+            self.missed_pongs += 1;
+        }
+        self.last_id += 1;
+        self.waiting_for = Some((self.last_id, now));
+        self.next_ping = now + interval;
+        Some(self.last_id)
+    }
+
+    /// Answers to pings that were already given up on are ignored
+    pub fn pong(&mut self, id: u64, now: Instant) {
+        if let Some((waiting_id, sent)) = self.waiting_for {
+            if waiting_id == id {
+                self.latency = Some(now.duration_since(sent));
+                self.waiting_for = None;
+                self.missed_pongs = 0;
+            }
+        }
+    }
+
+    pub fn is_dead(&self, max_missed_pongs: usize) -> bool {
+        self.missed_pongs >= max_missed_pongs
+    }
+}
+
+/// Drops control characters (so nobody can send terminal escapes to the others), trims the
+/// message and cuts it to MAX_CHAT_LEN. None when nothing is left.
+pub fn clean_chat_msg(msg: &str) -> Option<String> {
+    let msg = msg.chars().filter(|c| !c.is_control()).collect::<String>();
+    let msg = msg.trim();
+    match msg.is_empty() {
+        true => None,
+        false => Some(msg.chars().take(MAX_CHAT_LEN).collect()),
+    }
+}
+
+/// Reads the commands of the host, eg: `/kick Bob`. None when the line isn't one, so that it can
+/// be handled as something else.
+pub fn parse_host_command(input: &str) -> Option<Result<HostCommand, String>> {
+    let input = input.trim();
+    // Names can have spaces in them (eg: Red Penguin), so the argument goes on to the end of the line
+    let (command, argument) = input.split_once(char::is_whitespace).map_or((input, None), |(command, argument)| (command, Some(argument.trim())));
+    let command = match (command, argument) {
+        ("/start", None) => Ok(HostCommand::Start),
+        ("/lock", None) => Ok(HostCommand::Lock { locked: true }),
+        ("/unlock", None) => Ok(HostCommand::Lock { locked: false }),
+        ("/rules", Some(rules)) => RulesKind::from_str(rules, true)
+            .map(|rules| HostCommand::SetRules { rules })
+            .map_err(|_| format!("Unknown rules {}, try one of: official, house, flip, no-mercy", rules)),
+        ("/kick", Some(name)) => Ok(HostCommand::Kick { name: name.to_string() }),
+        ("/host", Some(name)) => Ok(HostCommand::MakeHost { name: name.to_string() }),
+        ("/bot", None) => Ok(HostCommand::AddBot { difficulty: Difficulty::Normal }),
+        ("/bot", Some(difficulty)) => Difficulty::from_str(difficulty, true)
+            .map(|difficulty| HostCommand::AddBot { difficulty })
+            .map_err(|_| format!("Unknown difficulty {}, try one of: easy, normal, hard, expert", difficulty)),
+        ("/rules" | "/kick" | "/host", None) => Err(format!("{} needs an argument", command)),
+        ("/start" | "/lock" | "/unlock", Some(_)) => Err(format!("{} takes no argument", command)),
+        _ => return None,
+    };
+    Some(command)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -71,11 +201,79 @@ mod tests {
         assert_eq!(name4, "Shellyy".to_string());
     }
 
+    #[test]
+    fn random_names_never_run_out() {
+        let mut names = Names::new();
+        let generated = (0..200).map(|_| names.get_random_name()).collect::<Vec<String>>();
+        let mut deduped = generated.clone();
+        deduped.sort();
+        deduped.dedup();
+        assert_eq!(generated.len(), deduped.len());
+    }
+
     #[test]
     fn invalid_name() {
         let mut names = Names::new();
         let name1 = names.get_specific_name("StaticESC#1234".to_string());
         assert!(name1.is_err());
+        assert!(names.get_specific_name("".to_string()).is_err());
+        assert!(names.get_specific_name("Two words".to_string()).is_err());
+        assert!(names.get_specific_name("\x1B[2J".to_string()).is_err());
+        assert!(names.get_specific_name("a".repeat(MAX_NAME_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn chat_rate_limit() {
+        let mut limiter = ChatLimiter::default();
+        let start = Instant::now();
+        assert!((0..CHAT_RATE).all(|_| limiter.allow(start)));
+        assert!(!limiter.allow(start + Duration::from_secs(1)));
+        assert!(limiter.allow(start + CHAT_WINDOW));
+    }
+
+    #[test]
+    fn chat_msg_cleanup() {
+        assert_eq!(clean_chat_msg("  hi\x1B[2J "), Some("hi[2J".to_string()));
+        assert_eq!(clean_chat_msg(" \n "), None);
+        assert_eq!(clean_chat_msg(&"a".repeat(MAX_CHAT_LEN + 10)).unwrap().len(), MAX_CHAT_LEN);
     }
-}
 
+    #[test]
+    fn heartbeat() {
+        let interval = Duration::from_secs(5);
+        let start = Instant::now();
+        let mut heartbeat = Heartbeat::new(start, interval);
+        assert_eq!(heartbeat.ping_due(start, interval), None);
+        let id = heartbeat.ping_due(start + interval, interval).unwrap();
+        heartbeat.pong(id, start + interval + Duration::from_millis(40));
+        assert_eq!(heartbeat.latency, Some(Duration::from_millis(40)));
+        // Two pings in a row go unanswered
+        let mut now = start + interval * 2;
+        heartbeat.ping_due(now, interval).unwrap();
+        now += interval;
+        let late_id = heartbeat.ping_due(now, interval).unwrap();
+        now += interval;
+        heartbeat.ping_due(now, interval).unwrap();
+        assert!(heartbeat.is_dead(2));
+        // A pong for a ping given up on doesn't bring the client back
+        heartbeat.pong(late_id, now);
+        assert!(heartbeat.is_dead(2));
+    }
+
+    #[test]
+    fn host_commands() {
+        assert_eq!(parse_host_command("/start"), Some(Ok(HostCommand::Start)));
+        assert_eq!(parse_host_command(" /kick Bob "), Some(Ok(HostCommand::Kick { name: "Bob".to_string() })));
+        assert_eq!(parse_host_command("/rules no-mercy"), Some(Ok(HostCommand::SetRules { rules: RulesKind::NoMercy })));
+        assert!(parse_host_command("/rules chess").unwrap().is_err());
+        assert!(parse_host_command("/host").unwrap().is_err());
+        assert_eq!(parse_host_command("/kick Red Penguin"), Some(Ok(HostCommand::Kick { name: "Red Penguin".to_string() })));
+        assert_eq!(parse_host_command("/host  Red Penguin"), Some(Ok(HostCommand::MakeHost { name: "Red Penguin".to_string() })));
+        assert!(parse_host_command("/start now").unwrap().is_err());
+        assert_eq!(parse_host_command("/bot"), Some(Ok(HostCommand::AddBot { difficulty: Difficulty::Normal })));
+        assert_eq!(parse_host_command("/bot HARD"), Some(Ok(HostCommand::AddBot { difficulty: Difficulty::Hard })));
+        assert!(parse_host_command("/bot genius").unwrap().is_err());
+        assert_eq!(parse_host_command("/say hi"), None);
+        assert_eq!(parse_host_command("3 red"), None);
+    }
+}
@@ -1,12 +1,25 @@
 /* Netcode of the game
  * - For client-server communications, the program uses packets sent over TCP. The packets are
- * essentially PACKET_SIZE sized buffers which are either RequestPacket (client -> server) or
- * ResponsePacket (server -> client) types which are seralized/deserialized on respective ends
+ * length-prefixed buffers which are either ClientPacket (client -> server) or ServerPacket
+ * (server -> client) types which are seralized/deserialized on respective ends. Length prefixing
+ * lets big tables (many players, large hands) send updates of any size
  *
  * - For intra-server communications, such serialization/deserialization isn't necessary, hence I
- * simply use Tokio channels for inter-thread communications
+ * simply use channels for inter-thread communications
+ *
+ * - One server hosts any number of rooms (see room.rs). Clients land in the lobby after picking a
+ * name, and from there list, create and join rooms
  */
 
+macro_rules! server_received_unexpected_packet {
+    () => {
+        bunt::println!("{$red}[{}]Server received unexpected packet from client{/$}", line!())
+    };
+}
+
 pub mod client_server;
 pub mod packets;
 pub mod misc;
+pub mod room;
+pub mod console;
+pub mod external;
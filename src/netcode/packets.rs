@@ -1,11 +1,17 @@
-use std::{net::TcpStream, io::{Read, Write}};
+use std::{net::TcpStream, io::{self, Read, Write}};
 
 use serde::{Serialize, Deserialize};
-use bincode::{ deserialize, serialize_into};
+use bincode::{ deserialize, serialize};
 
-use crate::card::{Hand, Color};
+use crate::bot::{Difficulty, Move};
+use crate::card::{Color, CardId};
+use crate::game::{GameSnapshot, TurnMoveError};
+use crate::netcode::room::RoomSummary;
+use crate::rules::RulesKind;
 
-const PACKET_SIZE : usize = 1024;
+/// Every packet is prefixed by its length as a big endian u32. Anything larger than this is
+/// treated as garbage rather than allocated.
+const MAX_PACKET_SIZE : usize = 1 << 20;
 
 pub trait TCPPacket{}
 
@@ -16,11 +22,25 @@ pub enum ServerPacket {
     AuthAcknowledged,
     AskPreferredName,
     SendGivenName {name : String, optional_msg: Option<String>},
+    SendRoomList {rooms: Vec<RoomSummary>},
+    /// The client left the lobby for this room
+    RoomJoined {room: String},
     SendMsg {msg: Option<String>},
-    SendMsgUpdate {msg_first_half: String, hand: Hand, msg_second_half : String, is_my_turn: bool},
-    SendMoveAcknowledgement {msg: Option<String>},
+    /// Sent to every player still in the game before each turn
+    SendGameState {snapshot: GameSnapshot},
+    /// None when the move was accepted
+    SendMoveAcknowledgement {error: Option<TurnMoveError>},
+    /// A chat message, sent to everyone (the sender included)
+    SendChat {from: String, msg: String},
     YouWon,
     YouLost,
+    /// The game of the room is over, and players can vote for a rematch
+    GameEnded,
+    /// Time left for the move of the player whose turn it is. Sent to everyone when a turn
+    /// starts, and again to that player when time is running out
+    TurnTimer {seconds: u64},
+    /// Has to be answered with a Pong carrying the same id, or the client is taken for gone
+    Ping {id: u64},
 }
 
 #[derive(Serialize, Deserialize)]
@@ -28,30 +48,80 @@ pub enum ServerPacket {
 pub enum ClientPacket {
     AuthResponse {join_code : usize},
     SendPreferredName {optional_client_name: Option<String>},
-    SendMoveCard {card_idx: usize, color_choice: Option<Color>},
+    /// Sent from the lobby
+    ListRooms,
+    /// Spectators get the table but no seat, and can join a game that has already started
+    JoinRoom {room: String, spectator: bool},
+    /// Creates a room and joins it as a player
+    CreateRoom {room: String, rules: RulesKind},
+    SendMoveCard {card_id: CardId, color_choice: Option<Color>, target_player: Option<usize>},
     SendMovePick,
+    /// Can be sent at any time, not only on the client's turn
+    SendChat {msg: String},
+    /// Sent after the game is over. The rematch starts once every player has voted
+    VoteRematch,
+    /// Only the host of the room may send these
+    HostCommand {command: HostCommand},
+    /// Answers a Ping, at any time
+    Pong {id: u64},
+}
+
+/// What the host of a room can do from their client, so that nobody has to watch the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum HostCommand {
+    Start,
+    /// Only between games
+    SetRules {rules: RulesKind},
+    /// Players are named as in the players list
+    Kick {name: String},
+    /// A locked room can't be joined, not even by spectators
+    Lock {locked: bool},
+    /// Makes another player the host
+    MakeHost {name: String},
+    /// Seats a built-in bot, only between games
+    AddBot {difficulty: Difficulty},
+}
+
+impl From<Move> for ClientPacket {
+    fn from(bot_move: Move) -> ClientPacket {
+        match bot_move {
+            // Players are numbered from 1 on the wire
+            Move::Card { card_id, color_choice, target_player } => {
+                ClientPacket::SendMoveCard { card_id, color_choice, target_player: target_player.map(|target| target + 1) }
+            }
+            Move::Pick => ClientPacket::SendMovePick,
+        }
+    }
 }
 
 impl TCPPacket for ClientPacket{}
 impl TCPPacket for ServerPacket{}
 
 
-pub fn read_packet<T : for<'a> Deserialize<'a> + TCPPacket>(stream : &mut TcpStream) -> T {
-    let mut buff = [0u8; PACKET_SIZE];
-    match stream.read_exact(&mut buff) {
-        Err(_) => bunt::println!("{$red}[{}] Error receiving packet!{/$}", line!()),
-        Ok(_) => {},
-        // Ok(_) => bunt::println!("{$green}Packet Received!{/$}"),
+/// Fails if the connection was lost, or if the peer sent something that isn't a packet of type T
+pub fn read_packet<T : for<'a> Deserialize<'a> + TCPPacket>(stream : &mut TcpStream) -> io::Result<T> {
+    let mut len_buff = [0u8; 4];
+    stream.read_exact(&mut len_buff)?;
+    let len = u32::from_be_bytes(len_buff) as usize;
+    if len > MAX_PACKET_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("received packet of {len} bytes, which is larger than the maximum allowed")));
     }
-    deserialize::<T>(&buff).unwrap()
+    let mut buff = vec![0u8; len];
+    stream.read_exact(&mut buff)?;
+    deserialize::<T>(&buff).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
 }
 
 pub fn send_packet<T : Serialize + TCPPacket>(stream : &mut TcpStream, packet : T) {
-    let mut buff = [0u8; PACKET_SIZE];
-    serialize_into(&mut buff[..], &packet).unwrap();
-    match stream.write_all(&buff[..PACKET_SIZE]){
-        Err(_) => bunt::println!("{$red}[{}] Error sending packet{/$}", line!()),
-        Ok(_) => {},
-        // Ok(_) => bunt::println!("{$green}Packet Sent!{/$}"),
-    };
+    if try_send_packet(stream, packet).is_err() {
+        bunt::println!("{$red}[{}] Error sending packet{/$}", line!());
+    }
+}
+
+/// Fails if the connection was lost, or if the stream has a write timeout that ran out
+pub fn try_send_packet<T : Serialize + TCPPacket>(stream : &mut TcpStream, packet : T) -> io::Result<()> {
+    let buff = serialize(&packet).unwrap();
+    let mut framed = Vec::with_capacity(buff.len() + 4);
+    framed.extend_from_slice(&(buff.len() as u32).to_be_bytes());
+    framed.extend_from_slice(&buff);
+    stream.write_all(&framed)
 }
@@ -0,0 +1,916 @@
+/* Rooms
+ * - A room is one table: its rules, the players seated at it, the spectators and the game.
+ * - The server (see client_server.rs) hands every packet of a client in a room over to
+ * Room::handle, one at a time.
+ */
+
+use std::{fmt, io, net::{Shutdown, TcpStream}, path::Path, sync::{Arc, atomic::{AtomicUsize, Ordering}, mpsc::{self, Receiver, Sender, TryRecvError}}, thread, time::{Duration, Instant}};
+
+use serde::{Serialize, Deserialize};
+
+use crate::bot::{self, Difficulty, Move};
+use crate::card::Deck;
+use crate::game::{GameState, MoveOutcome, TurnMoveError};
+use crate::netcode::external::ExternalBot;
+use crate::netcode::misc::{ChatLimiter, Heartbeat, clean_chat_msg};
+use crate::netcode::packets::{try_send_packet, ClientPacket, HostCommand, ServerPacket};
+use crate::rules::{RuleSet, RulesKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub enum GamePhase {
+    Waiting,
+    InGame,
+    GameOver,
+}
+
+/// Players who run out of time this many turns in a row are flagged as AFK
+const AFK_TIMEOUTS : usize = 3;
+/// Time AFK players get for their move, so that they don't hold the table up
+const AFK_TURN_TIME : Duration = Duration::from_secs(5);
+/// Players are reminded of the time left once this much is left
+const TIMER_WARNING : Duration = Duration::from_secs(10);
+
+/// Identifies a connection for as long as the server runs. Seats can't be used for this since
+/// they move around when someone leaves the lobby.
+pub type ClientId = usize;
+
+/// Bots get their ids from the same counter as connections, so that they can't be mixed up
+static NEXT_CLIENT_ID : AtomicUsize = AtomicUsize::new(0);
+
+pub fn next_client_id() -> ClientId {
+    NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// How the server reaches whoever sits in a seat
+#[derive(Debug)]
+pub enum Connection {
+    Tcp(TcpStream),
+    /// Built-in bots play from the server's own copy of the game, nothing is sent to them
+    Bot(Difficulty),
+    /// A program started by the server, see external.rs
+    External(ExternalBot),
+}
+
+#[derive(Debug)]
+pub struct ClientInfo {
+    pub id: ClientId,
+    pub connection: Connection,
+    pub name: String,
+    /// False once nothing can be read from the client anymore, or once they are kicked
+    pub connected: bool,
+    pub chat_limiter: ChatLimiter,
+    /// Turns in a row the player ran out of time on
+    pub timeouts: usize,
+    pub afk: bool,
+    pub heartbeat: Heartbeat,
+}
+
+impl ClientInfo {
+    pub fn new(id: ClientId, stream: TcpStream, name: String, ping_interval: Duration) -> ClientInfo {
+        ClientInfo {
+            id, connection: Connection::Tcp(stream), name, connected: true, chat_limiter: ChatLimiter::default(), timeouts: 0, afk: false,
+            heartbeat: Heartbeat::new(Instant::now(), ping_interval),
+        }
+    }
+
+    pub fn new_bot(name: String, difficulty: Difficulty) -> ClientInfo {
+        ClientInfo {
+            id: next_client_id(), connection: Connection::Bot(difficulty), name, connected: true, chat_limiter: ChatLimiter::default(),
+            timeouts: 0, afk: false, heartbeat: Heartbeat::new(Instant::now(), Duration::ZERO),
+        }
+    }
+
+    pub fn new_external(id: ClientId, name: String, bot: ExternalBot) -> ClientInfo {
+        ClientInfo {
+            id, connection: Connection::External(bot), name, connected: true, chat_limiter: ChatLimiter::default(),
+            timeouts: 0, afk: false, heartbeat: Heartbeat::new(Instant::now(), Duration::ZERO),
+        }
+    }
+
+    /// Built-in and external bots
+    pub fn is_bot(&self) -> bool {
+        !matches!(self.connection, Connection::Tcp(_))
+    }
+
+    /// The difficulty of a built-in bot
+    pub fn bot(&self) -> Option<Difficulty> {
+        match self.connection {
+            Connection::Bot(difficulty) => Some(difficulty),
+            _ => None,
+        }
+    }
+
+    pub fn send(&mut self, packet: ServerPacket) {
+        let sent = match &mut self.connection {
+            _ if !self.connected => Ok(()),
+            Connection::Tcp(stream) => try_send_packet(stream, packet),
+            Connection::Bot(_) => Ok(()),
+            Connection::External(bot) => bot.send(&packet),
+        };
+        // Part of the packet may have gone out, so the connection can't be used anymore
+        if let Err(e) = sent {
+            bunt::println!("{$yellow}Could not send to {} ({}), disconnecting them{/$}", self.name, e);
+            self.disconnect();
+        }
+    }
+
+    /// Closes the connection. The reader thread then notices, and the client is removed like
+    /// anyone who leaves.
+    pub fn disconnect(&mut self) {
+        match &mut self.connection {
+            Connection::Tcp(stream) => {let _ = stream.shutdown(Shutdown::Both);}
+            Connection::Bot(_) => {},
+            Connection::External(bot) => bot.kill(),
+        }
+        self.connected = false;
+    }
+}
+
+/// How a room plays, besides its rules
+#[derive(Debug, Clone, Copy)]
+pub struct RoomSettings {
+    /// Number of decks to play with. When None, it is picked from the number of players
+    pub num_decks: Option<usize>,
+    /// Whether the first player moves to the last seat on a rematch
+    pub rotate_first: bool,
+    /// How long players have for their move. None to wait for as long as it takes
+    pub turn_time: Option<Duration>,
+    /// Players who run out of time get a move played for them, rather than picking up
+    pub stand_in: bool,
+    /// How long expert bots think about a move
+    pub bot_budget: Duration,
+    /// How long external bots have for their move
+    pub bot_time_limit: Duration,
+}
+
+/// A packet read by a client's reader thread, or the reason it stopped reading
+pub struct Incoming {
+    pub client_id: ClientId,
+    pub packet: io::Result<ClientPacket>,
+}
+
+/// What the lobby shows about a room
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomSummary {
+    pub name: String,
+    pub rules: RulesKind,
+    pub num_players: usize,
+    pub num_spectators: usize,
+    pub game_phase: GamePhase,
+    pub host: Option<String>,
+    pub locked: bool,
+}
+
+impl fmt::Display for RoomSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self.game_phase {
+            GamePhase::Waiting => "waiting for players",
+            GamePhase::InGame => "playing",
+            GamePhase::GameOver => "game over",
+        };
+        write!(f, "{:<24} {:<9} {} player(s), {} spectator(s), {}", self.name, format!("{:?}", self.rules), self.num_players, self.num_spectators, status)?;
+        if let Some(host) = &self.host {
+            write!(f, ", hosted by {}", host)?;
+        }
+        if self.locked {
+            write!(f, ", locked")?;
+        }
+        Ok(())
+    }
+}
+
+/// One table of the server, with its own rules, players and game
+pub struct Room {
+    pub name: String,
+    pub game_phase: GamePhase,
+    settings: RoomSettings,
+    /// Shared with the bots while they think
+    rules: Arc<dyn RuleSet>,
+    /// Seats are in the same order as state.players
+    pub clients_info: Vec<ClientInfo>,
+    /// Watch the game without a seat, and can come in at any time
+    pub spectators: Vec<ClientInfo>,
+    /// None until the game is started
+    pub state: Option<GameState>,
+    /// Players who want to play again, once the game is over
+    rematch_votes: Vec<ClientId>,
+    /// The first player to join, until they hand the role over or leave
+    host: Option<ClientId>,
+    /// Nobody can join a locked room
+    pub locked: bool,
+    /// Moves are turned down while the game is paused
+    pub paused: bool,
+    /// The seat the turn timer runs for, and since when
+    timer_seat: Option<usize>,
+    turn_started: Instant,
+    /// Whether the player was reminded of the time left on this turn
+    timer_warned: bool,
+    /// When the bot whose turn it is starts thinking about its move
+    bot_move_at: Option<Instant>,
+    /// Where the move of the bot comes from once it is found
+    bot_thinking: Option<Receiver<Move>>,
+}
+
+/// What `save` writes for a room
+#[derive(Serialize, Deserialize)]
+pub struct SavedRoom {
+    pub name: String,
+    pub rules: RulesKind,
+    pub game_phase: GamePhase,
+    pub players: Vec<String>,
+    pub spectators: Vec<String>,
+    pub state: Option<GameState>,
+}
+
+impl Room {
+    pub fn new(name: String, rules: Box<dyn RuleSet>, settings: RoomSettings) -> Room {
+        Room {
+            name, game_phase: GamePhase::Waiting, settings, rules: rules.into(), clients_info: vec![], spectators: vec![], state: None,
+            rematch_votes: vec![], host: None, locked: false, paused: false,
+            timer_seat: None, turn_started: Instant::now(), timer_warned: false, bot_move_at: None, bot_thinking: None,
+        }
+    }
+
+    pub fn summary(&self) -> RoomSummary {
+        RoomSummary {
+            name: self.name.clone(),
+            rules: self.rules.kind(),
+            num_players: self.clients_info.iter().filter(|client| client.connected).count(),
+            num_spectators: self.spectators.len(),
+            game_phase: self.game_phase,
+            host: self.host.and_then(|host| self.seat_of(host)).map(|seat| self.clients_info[seat].name.clone()),
+            locked: self.locked,
+        }
+    }
+
+    pub fn has_client(&self, client_id: ClientId) -> bool {
+        self.clients_info.iter().chain(self.spectators.iter()).any(|client| client.id == client_id)
+    }
+
+    /// Bots don't count, nor do players who left during the game
+    pub fn is_empty(&self) -> bool {
+        !self.clients_info.iter().any(|client| client.connected && !client.is_bot()) && self.spectators.is_empty()
+    }
+
+    /// Seats a player (only while no game is being played) or adds a spectator (at any time)
+    pub fn add_client(&mut self, mut client: ClientInfo, spectator: bool) {
+        client.send(ServerPacket::RoomJoined { room: self.name.clone() });
+        if self.game_phase == GamePhase::GameOver {
+            client.send(ServerPacket::GameEnded);
+        }
+        if !spectator {
+            bunt::println!("{$green}{} has joined the game in {}!{/$}", client.name, self.name);
+            self.clients_info.push(client);
+            if self.host.is_none() {
+                self.pass_host_to(self.clients_info.len() - 1);
+            }
+            return;
+        }
+        bunt::println!("{$green}{} is watching the game in {}!{/$}", client.name, self.name);
+        // Latecomers get the table right away rather than after the next move
+        if self.game_phase == GamePhase::InGame {
+            let snapshot = self.state.as_ref().unwrap().snapshot(self.rules.as_ref(), None, &self.labels());
+            client.send(ServerPacket::SendGameState { snapshot });
+        }
+        self.spectators.push(client);
+    }
+
+    /// Seats a built-in bot, named after its difficulty
+    pub fn add_bot(&mut self, difficulty: Difficulty) -> Result<(), String> {
+        let name = self.bot_name(&format!("{:?}-bot", difficulty).to_lowercase())?;
+        self.seat_bot(ClientInfo::new_bot(name, difficulty));
+        Ok(())
+    }
+
+    /// Starts `command` and seats it, named after its last word (the script, in `python3 bot.py`).
+    /// `tx` is where the moves it sends go, like those of the clients.
+    pub fn add_external(&mut self, command: &[&str], tx: Sender<Incoming>) -> Result<(), String> {
+        let (Some(program), Some(last)) = (command.first(), command.last()) else {
+            return Err("A command to run is needed".to_string());
+        };
+        let base_name = Path::new(last).file_stem().map_or("bot".to_string(), |stem| stem.to_string_lossy().replace(char::is_whitespace, "_"));
+        let name = self.bot_name(&base_name)?;
+        let client_id = next_client_id();
+        let bot = ExternalBot::spawn(command, client_id, tx).map_err(|e| format!("{} could not be started: {}", program, e))?;
+        self.seat_bot(ClientInfo::new_external(client_id, name, bot));
+        Ok(())
+    }
+
+    /// A name for a bot that nobody at the table has, only between games
+    fn bot_name(&self, base_name: &str) -> Result<String, String> {
+        if self.game_phase == GamePhase::InGame {
+            return Err("Bots can only be added between games.".to_string());
+        }
+        let name = (1..).map(|n| if n == 1 {base_name.to_string()} else {format!("{}-{}", base_name, n)})
+            .find(|name| self.clients_info.iter().all(|client| &client.name != name))
+            .unwrap();
+        Ok(name)
+    }
+
+    fn seat_bot(&mut self, bot: ClientInfo) {
+        let msg = format!("{} has joined the game", bot.name);
+        bunt::println!("{$green}{} has joined the game in {}!{/$}", bot.name, self.name);
+        self.clients_info.push(bot);
+        self.announce(&msg);
+    }
+
+    /// Whether the game can be started now, and if not why
+    pub fn check_can_start(&self) -> Result<(), String> {
+        if self.game_phase == GamePhase::InGame {
+            return Err("Game cannot be started if it already has started".to_string());
+        }
+        if self.clients_info.len() < 2 {
+            return Err("Game cannot be started if number of players less than 2".to_string());
+        }
+        self.check_deck_size()
+    }
+
+    fn num_decks(&self) -> usize {
+        self.settings.num_decks.unwrap_or_else(|| Deck::decks_for_players(self.clients_info.len()))
+    }
+
+    /// Makes sure there are enough cards to deal every hand and turn the first card
+    fn check_deck_size(&self) -> Result<(), String> {
+        let total_cards = self.rules.cards_per_deck() * self.num_decks();
+        let needed_cards = self.clients_info.len() * 7 + 1;
+        if total_cards < needed_cards {
+            return Err(format!("The deck has {} cards, but {} are needed to deal {} players. Use more decks (-d) or a bigger deck.",
+                               total_cards, needed_cards, self.clients_info.len()));
+        }
+        Ok(())
+    }
+
+    /// Builds the deck for the players that have joined, deals their hands and turns the first card
+    fn deal(&mut self) {
+        let num_decks = self.num_decks();
+        let state = GameState::new(self.rules.as_ref(), self.clients_info.len(), num_decks);
+        bunt::println!("{$magenta}Playing with {} deck(s), {} cards left to draw after dealing{/$}", num_decks, state.deck.len());
+        self.state = Some(state);
+    }
+
+    /// Starts the first game of the room, or a rematch
+    pub fn start_game(&mut self) {
+        if self.state.is_some() && self.settings.rotate_first {
+            self.clients_info.rotate_left(1);
+        }
+        self.rematch_votes.clear();
+        self.deal();
+        self.game_phase = GamePhase::InGame;
+        bunt::println!("{$magenta}Game Started in {}!{/$}", self.name);
+        self.send_game_states();
+        self.start_turn_timer();
+    }
+
+    fn seat_of(&self, client_id: ClientId) -> Option<usize> {
+        self.clients_info.iter().position(|client| client.id == client_id)
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.clients_info.iter().map(|client| client.name.clone()).collect()
+    }
+
+    /// Names as shown in the players list
+    fn labels(&self) -> Vec<String> {
+        self.clients_info.iter().map(|client| if client.afk {format!("{} (AFK)", client.name)} else {client.name.clone()}).collect()
+    }
+
+    /// Players and spectators that can still be reached
+    fn everyone(&mut self) -> impl Iterator<Item = &mut ClientInfo> {
+        self.clients_info.iter_mut().chain(self.spectators.iter_mut()).filter(|client| client.connected)
+    }
+
+    /// Sends every player still in the game what they get to see of it, and the spectators what
+    /// is on the table
+    fn send_game_states(&mut self) {
+        let state = self.state.as_ref().unwrap();
+        let names = self.labels();
+        let latencies = self.clients_info.iter().map(|client| client.heartbeat.latency.map(|latency| latency.as_millis() as u64)).collect::<Vec<Option<u64>>>();
+        let snapshot = |player: Option<usize>| {
+            let mut snapshot = state.snapshot(self.rules.as_ref(), player, &names);
+            snapshot.players.iter_mut().zip(&latencies).for_each(|(player, &latency_ms)| player.latency_ms = latency_ms);
+            snapshot
+        };
+        for (idx, client) in self.clients_info.iter_mut().enumerate() {
+            if !state.players[idx].is_active || !client.connected {continue;}
+            client.send(ServerPacket::SendGameState { snapshot: snapshot(Some(idx)) });
+        }
+        let snapshot = snapshot(None);
+        for spectator in self.spectators.iter_mut().filter(|spectator| spectator.connected) {
+            spectator.send(ServerPacket::SendGameState { snapshot: snapshot.clone() });
+        }
+        // Everyone now knows the turn, so a bot whose turn it is can start thinking
+        let bot = self.clients_info[state.turn].bot().filter(|_| self.game_phase == GamePhase::InGame);
+        self.bot_move_at = bot.map(|difficulty| Instant::now() + difficulty.think_time());
+        self.bot_thinking = None;
+    }
+
+    /// Tells everyone, spectators included, what happened in the game
+    fn announce(&mut self, msg: &str) {
+        for client in self.everyone() {
+            client.send(ServerPacket::SendMsg { msg: Some(msg.to_string()) });
+        }
+    }
+
+    /// Ends the game once a single player is left. They lose, unless everyone else was eliminated
+    /// (No Mercy), in which case they are the last one standing and win.
+    /// Returns whether the game is over.
+    fn end_game_if_over(&mut self) -> bool {
+        let state = self.state.as_ref().unwrap();
+        let Some(id) = state.is_game_over() else {return false};
+        bunt::println!("{$yellow}All but one clients are inactive, Game Over!{/$}");
+        let packet = if state.last_one_standing() {ServerPacket::YouWon} else {ServerPacket::YouLost};
+        let points = state.hand_points(self.rules.as_ref(), id);
+        let msg = format!("{} was left with {} points in hand.", self.clients_info[id].name, points);
+        println!("{}", msg);
+        self.announce(&msg);
+        self.clients_info[id].send(packet);
+        self.finish_game();
+        true
+    }
+
+    /// Ends the game for good, even if players are still in it
+    pub fn end_game(&mut self) -> Result<(), String> {
+        if self.game_phase != GamePhase::InGame {
+            return Err(format!("There is no game being played in {}", self.name));
+        }
+        bunt::println!("{$yellow}The game in {} was ended{/$}", self.name);
+        self.announce("The game was ended by the server.");
+        self.finish_game();
+        Ok(())
+    }
+
+    /// Opens the rematch vote
+    fn finish_game(&mut self) {
+        self.game_phase = GamePhase::GameOver;
+        self.paused = false;
+        bunt::println!("{$yellow}Game has ended in {}. Waiting for a rematch vote{/$}", self.name);
+        // Seats only have to match the players of the game while it is played
+        self.clients_info.retain(|client| client.connected);
+        for client in self.everyone() {
+            client.send(ServerPacket::GameEnded);
+        }
+    }
+
+    pub fn set_paused(&mut self, paused: bool) -> Result<(), String> {
+        if self.game_phase != GamePhase::InGame {
+            return Err(format!("There is no game being played in {}", self.name));
+        }
+        if self.paused == paused {
+            return Err(format!("The game in {} is already {}", self.name, if paused {"paused"} else {"running"}));
+        }
+        self.paused = paused;
+        bunt::println!("{$yellow}The game in {} is {}{/$}", self.name, if paused {"paused"} else {"resumed"});
+        self.announce(if paused {"The game was paused by the server."} else {"The game goes on!"});
+        // Whoever had their move turned down gets to play it now, with the full time
+        if !paused {
+            self.send_game_states();
+            self.start_turn_timer();
+        }
+        Ok(())
+    }
+
+    /// The player whose turn it is picks up as if they had typed 'p', for when they are stuck
+    pub fn skip_turn(&mut self) -> Result<(), String> {
+        let Some(state) = self.state.as_ref().filter(|_| self.game_phase == GamePhase::InGame) else {
+            return Err(format!("There is no game being played in {}", self.name));
+        };
+        let seat = state.turn;
+        let msg = format!("The server skipped the turn of {}", self.clients_info[seat].name);
+        bunt::println!("{$yellow}{}{/$}", msg);
+        self.announce(&msg);
+        self.play_move(seat, ClientPacket::SendMovePick);
+        Ok(())
+    }
+
+    /// Passes a message of the server on to everyone in the room
+    pub fn broadcast(&mut self, msg: &str) {
+        for client in self.everyone() {
+            client.send(ServerPacket::SendChat { from: "[server]".to_string(), msg: msg.to_string() });
+        }
+    }
+
+    /// Only between games
+    pub fn set_rules(&mut self, rules: RulesKind) -> Result<(), String> {
+        if self.game_phase == GamePhase::InGame {
+            return Err("The rules can't be changed during a game.".to_string());
+        }
+        // A custom deck of the server is left out, as it may not suit the new rules
+        self.rules = rules.rule_set(rules.default_deck_definition()).into();
+        let msg = format!("The rules of {} are now {:?}", self.name, rules);
+        bunt::println!("{$yellow}{}{/$}", msg);
+        self.announce(&msg);
+        Ok(())
+    }
+
+    pub fn save(&self) -> SavedRoom {
+        SavedRoom {
+            name: self.name.clone(),
+            rules: self.rules.kind(),
+            game_phase: self.game_phase,
+            players: self.names(),
+            spectators: self.spectators.iter().map(|spectator| spectator.name.clone()).collect(),
+            state: self.state.clone(),
+        }
+    }
+
+    /// The players of the room as a table, for the server console
+    pub fn status_table(&self) -> String {
+        let summary = self.summary();
+        let mut ret_string = summary.to_string();
+        if self.paused {
+            ret_string += ", paused";
+        }
+        ret_string += &format!("\n  {:<5} {:<24} {:<6} {}\n", "Seat", "Name", "Cards", "Ping");
+        let state = self.state.as_ref().filter(|_| self.game_phase == GamePhase::InGame);
+        for (seat, client) in self.clients_info.iter().enumerate() {
+            let player = state.map(|state| &state.players[seat]);
+            let turn = if state.is_some_and(|state| state.turn == seat) {">"} else {" "};
+            let cards = player.map_or("-".to_string(), |player| player.hand.len().to_string());
+            let ping = client.heartbeat.latency.map_or("-".to_string(), |latency| format!("{}ms", latency.as_millis()));
+            let mut notes = vec![];
+            if Some(client.id) == self.host {notes.push("host")}
+            if !client.connected {notes.push("left")}
+            if client.is_bot() {notes.push("bot")}
+            if client.afk {notes.push("afk")}
+            if player.is_some_and(|player| player.is_eliminated) {notes.push("out")}
+            else if player.is_some_and(|player| !player.is_active) {notes.push("won")}
+            let row = format!("{} {:<5} {:<24} {:<6} {:<7} {}", turn, seat + 1, client.name, cards, ping, notes.join(", "));
+            ret_string += row.trim_end();
+            ret_string += "\n";
+        }
+        if !self.spectators.is_empty() {
+            ret_string += &format!("Spectators: {}\n", self.spectators.iter().map(|spectator| spectator.name.as_str()).collect::<Vec<&str>>().join(", "));
+        }
+        if let Some(state) = state {
+            ret_string += &format!("Top card: {}, {} card(s) left to draw\n", state.top_card(), state.deck.len());
+        }
+        ret_string
+    }
+
+    pub fn handle(&mut self, incoming: Incoming) {
+        if let Some(idx) = self.spectators.iter().position(|spectator| spectator.id == incoming.client_id) {
+            self.handle_spectator(idx, incoming.packet);
+            return;
+        }
+        // Whoever sent this may have left the room in the meantime
+        let Some(seat) = self.seat_of(incoming.client_id) else {return};
+        match incoming.packet {
+            Err(e) => self.client_left(seat, e),
+            Ok(ClientPacket::SendChat { msg }) => self.chat(incoming.client_id, msg),
+            Ok(ClientPacket::VoteRematch) => self.vote_rematch(seat),
+            Ok(ClientPacket::HostCommand { command }) => {
+                match self.host == Some(incoming.client_id) {
+                    true => self.host_command(seat, command),
+                    false => self.clients_info[seat].send(ServerPacket::SendMsg { msg: Some("Only the host can do that.".to_string()) }),
+                }
+            }
+            Ok(packet @ (ClientPacket::SendMoveCard { .. } | ClientPacket::SendMovePick)) => {
+                let is_their_turn = self.game_phase == GamePhase::InGame && self.state.as_ref().is_some_and(|state| state.turn == seat);
+                match (is_their_turn, self.paused) {
+                    (true, false) => {
+                        self.back_from_afk(seat);
+                        self.play_move(seat, packet);
+                    }
+                    (true, true) => self.clients_info[seat].send(ServerPacket::SendMoveAcknowledgement { error: Some(TurnMoveError::GamePaused) }),
+                    (false, _) => self.clients_info[seat].send(ServerPacket::SendMoveAcknowledgement { error: Some(TurnMoveError::NotYourTurn) }),
+                }
+            }
+            Ok(_) => {
+                server_received_unexpected_packet!();
+                self.clients_info[seat].send(ServerPacket::SendMoveAcknowledgement { error: Some(TurnMoveError::UnexpectedPacket) });
+            }
+        }
+    }
+
+    /// Spectators can chat, anything else they send is turned down
+    fn handle_spectator(&mut self, idx: usize, packet: io::Result<ClientPacket>) {
+        match packet {
+            Err(_) => {
+                let spectator = self.spectators.remove(idx);
+                bunt::println!("{$yellow}{} stopped watching{/$}", spectator.name);
+            }
+            Ok(ClientPacket::SendChat { msg }) => self.chat(self.spectators[idx].id, msg),
+            Ok(ClientPacket::VoteRematch) => {
+                let msg = "Only players can vote for a rematch.".to_string();
+                self.spectators[idx].send(ServerPacket::SendMsg { msg: Some(msg) });
+            }
+            Ok(ClientPacket::HostCommand { .. }) => {
+                self.spectators[idx].send(ServerPacket::SendMsg { msg: Some("Only the host can do that.".to_string()) });
+            }
+            Ok(_) => self.spectators[idx].send(ServerPacket::SendMoveAcknowledgement { error: Some(TurnMoveError::NotYourTurn) }),
+        }
+    }
+
+    /// Plays the move of the player whose turn it is. Returns false if the move was turned down,
+    /// in which case only the player is told and the game carries on as it was.
+    fn play_move(&mut self, seat: usize, packet: ClientPacket) -> bool {
+        let rules = self.rules.as_ref();
+        let state = self.state.as_mut().unwrap();
+        let curr_client = &mut self.clients_info[seat];
+        let cards_before = state.players[seat].hand.len();
+        // What the others are told about the move
+        let (outcome, log, eliminated) = match packet {
+            ClientPacket::SendMoveCard { card_id, color_choice, target_player } => {
+                // target_player is 1 based, as shown in the players list
+                let target_player = target_player.map(|target| target.wrapping_sub(1));
+                match state.play_card(rules, card_id, color_choice, target_player) {
+                    Ok((outcome, eliminated)) => {
+                        curr_client.send(ServerPacket::SendMoveAcknowledgement { error: None });
+                        (outcome, Some(format!("{} played {}", curr_client.name, state.table_view().top_card)), eliminated)
+                    }
+                    Err(e) => {
+                        curr_client.send(ServerPacket::SendMoveAcknowledgement { error: Some(e) });
+                        (MoveOutcome::Continue, None, vec![])
+                    }
+                }
+            }
+            _ => {
+                let outcome = state.pick(rules);
+                let picked = state.players[seat].hand.len().saturating_sub(cards_before);
+                (outcome, Some(format!("{} picked up {} card(s)", curr_client.name, picked)), vec![])
+            }
+        };
+        let Some(log) = log else {return false};
+        self.announce(&log);
+        match outcome {
+            MoveOutcome::Continue => {},
+            MoveOutcome::Won => self.clients_info[seat].send(ServerPacket::YouWon),
+            MoveOutcome::Eliminated => self.knock_out(seat),
+        }
+        // Hands that were passed on or swapped can put others over the limit as well
+        for other in eliminated {
+            self.knock_out(other);
+        }
+        if !self.end_game_if_over() {
+            self.send_game_states();
+            self.start_turn_timer();
+        }
+        true
+    }
+
+    /// Tells everyone that the player in `seat` broke the mercy rule
+    fn knock_out(&mut self, seat: usize) {
+        let client = &mut self.clients_info[seat];
+        let msg = format!("{} has too many cards and is out of the game!", client.name);
+        bunt::println!("{$yellow}{}{/$}", msg);
+        client.send(ServerPacket::YouLost);
+        self.announce(&msg);
+    }
+
+    /// Restarts the clock for whoever's turn it is, and tells everyone how long they have
+    fn start_turn_timer(&mut self) {
+        let Some(state) = self.state.as_ref() else {return};
+        if self.game_phase != GamePhase::InGame {
+            return;
+        }
+        let turn = state.turn;
+        self.timer_seat = Some(turn);
+        self.turn_started = Instant::now();
+        self.timer_warned = false;
+        let Some(limit) = self.turn_limit(turn) else {return};
+        let seconds = limit.as_secs();
+        for client in self.everyone() {
+            client.send(ServerPacket::TurnTimer { seconds });
+        }
+    }
+
+    /// How long the player in `seat` has for their move, if there is a limit. External bots
+    /// always have one, so that a program that hangs doesn't hold the table up.
+    fn turn_limit(&self, seat: usize) -> Option<Duration> {
+        let client = &self.clients_info[seat];
+        let limit = match client.connection {
+            Connection::External(_) => Some(self.settings.turn_time.map_or(self.settings.bot_time_limit, |turn_time| turn_time.min(self.settings.bot_time_limit))),
+            _ => self.settings.turn_time,
+        }?;
+        Some(if client.afk {limit.min(AFK_TURN_TIME)} else {limit})
+    }
+
+    /// Called every so often by the server to run the turn timer and the bots
+    pub fn tick(&mut self, now: Instant) {
+        self.play_bot(now);
+        let Some(state) = self.state.as_ref() else {return};
+        if self.game_phase != GamePhase::InGame || self.paused {
+            return;
+        }
+        // The turn also moves on without a move, eg: when the player whose turn it was left
+        if self.timer_seat != Some(state.turn) {
+            self.start_turn_timer();
+            return;
+        }
+        let turn = state.turn;
+        let Some(limit) = self.turn_limit(turn) else {return};
+        let client = &mut self.clients_info[turn];
+        let elapsed = now.saturating_duration_since(self.turn_started);
+        if elapsed >= limit {
+            self.time_out(turn);
+        }
+        else if !self.timer_warned && limit > TIMER_WARNING && limit - elapsed <= TIMER_WARNING {
+            self.timer_warned = true;
+            let seconds = (limit - elapsed).as_secs_f64().ceil() as u64;
+            client.send(ServerPacket::TurnTimer { seconds });
+        }
+    }
+
+    /// Gets the bot whose turn it is thinking once it has waited a little, and plays its move
+    /// once it is found. Expert bots take a while, so they think on a thread of their own rather
+    /// than holding up the server.
+    fn play_bot(&mut self, now: Instant) {
+        if self.paused || self.game_phase != GamePhase::InGame {
+            return;
+        }
+        if let Some(thinking) = &self.bot_thinking {
+            let bot_move = match thinking.try_recv() {
+                Ok(bot_move) => bot_move,
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => Move::Pick,
+            };
+            self.bot_thinking = None;
+            let seat = self.state.as_ref().unwrap().turn;
+            // The bot isn't asked again, so it picks up rather than hold the table up
+            if !self.play_move(seat, bot_move.into()) {
+                self.play_move(seat, ClientPacket::SendMovePick);
+            }
+            return;
+        }
+        let Some(_) = self.bot_move_at.filter(|&move_at| now >= move_at) else {return};
+        self.bot_move_at = None;
+        let state = self.state.as_ref().unwrap();
+        let Some(difficulty) = self.clients_info[state.turn].bot() else {return};
+        let (rules, state, budget) = (self.rules.clone(), state.clone(), self.settings.bot_budget);
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx.send(difficulty.choose_move(rules.as_ref(), &state, budget));
+        });
+        self.bot_thinking = Some(rx);
+    }
+
+    /// The player in `seat` ran out of time: they pick up, or the stand-in plays for them
+    fn time_out(&mut self, seat: usize) {
+        let client = &mut self.clients_info[seat];
+        client.timeouts += 1;
+        let msg = format!("{} ran out of time", client.name);
+        bunt::println!("{$yellow}{}{/$}", msg);
+        self.announce(&msg);
+        let client = &mut self.clients_info[seat];
+        if client.timeouts >= AFK_TIMEOUTS && !client.afk {
+            client.afk = true;
+            let msg = format!("{} is AFK, their turns are kept short until they are back", client.name);
+            bunt::println!("{$yellow}{}{/$}", msg);
+            self.announce(&msg);
+        }
+        let packet = match self.settings.stand_in {
+            true => bot::greedy_move(self.rules.as_ref(), self.state.as_ref().unwrap()).into(),
+            false => ClientPacket::SendMovePick,
+        };
+        if !self.play_move(seat, packet) {
+            self.play_move(seat, ClientPacket::SendMovePick);
+        }
+    }
+
+    /// A player who makes a move themselves is no longer AFK
+    fn back_from_afk(&mut self, seat: usize) {
+        let client = &mut self.clients_info[seat];
+        client.timeouts = 0;
+        if client.afk {
+            client.afk = false;
+            let msg = format!("{} is back", client.name);
+            bunt::println!("{$green}{}{/$}", msg);
+            self.announce(&msg);
+        }
+    }
+
+    /// Makes the player in `seat` the host and tells everyone
+    fn pass_host_to(&mut self, seat: usize) {
+        let host = &mut self.clients_info[seat];
+        self.host = Some(host.id);
+        let msg = "You are the host. Commands: /start, /rules <RULES>, /bot [LEVEL], /kick <NAME>, /lock, /unlock, /host <NAME>".to_string();
+        host.send(ServerPacket::SendMsg { msg: Some(msg) });
+        let msg = format!("{} is the host of {}", host.name, self.name);
+        bunt::println!("{$green}{}{/$}", msg);
+        self.announce(&msg);
+    }
+
+    fn host_command(&mut self, seat: usize, command: HostCommand) {
+        let result = match command {
+            HostCommand::Start => self.check_can_start().map(|()| self.start_game()),
+            HostCommand::SetRules { rules } => self.set_rules(rules),
+            HostCommand::Kick { name } if name == self.clients_info[seat].name => Err("The host can't be kicked.".to_string()),
+            HostCommand::Kick { name } => self.kick(&name),
+            HostCommand::Lock { locked } => {
+                self.locked = locked;
+                let msg = format!("{} is now {}", self.name, if locked {"locked"} else {"open"});
+                bunt::println!("{$yellow}{}{/$}", msg);
+                self.announce(&msg);
+                Ok(())
+            }
+            HostCommand::AddBot { difficulty } => self.add_bot(difficulty),
+            HostCommand::MakeHost { name } => {
+                self.clients_info.iter().position(|client| client.name == name && client.connected && !client.is_bot())
+                    .map(|new_seat| self.pass_host_to(new_seat))
+                    .ok_or(format!("There is no player called {}.", name))
+            }
+        };
+        if let Err(e) = result {
+            self.clients_info[seat].send(ServerPacket::SendMsg { msg: Some(e) });
+        }
+    }
+
+    /// Closes the connection of a player or spectator. They are then removed like anyone who
+    /// leaves, once their reader thread notices. Bots have no reader thread, so they leave at once.
+    pub fn kick(&mut self, name: &str) -> Result<(), String> {
+        if let Some(seat) = self.clients_info.iter().position(|client| client.name == name && client.connected && client.bot().is_some()) {
+            self.announce(&format!("{} was kicked", name));
+            self.client_left(seat, io::Error::other("kicked"));
+            return Ok(());
+        }
+        let Some(client) = self.clients_info.iter_mut().chain(self.spectators.iter_mut()).find(|client| client.name == name && client.connected) else {
+            return Err(format!("There is no player called {}.", name));
+        };
+        client.send(ServerPacket::SendMsg { msg: Some("You were kicked from the room.".to_string()) });
+        client.disconnect();
+        let msg = format!("{} was kicked", name);
+        bunt::println!("{$yellow}{}{/$}", msg);
+        self.announce(&msg);
+        Ok(())
+    }
+
+    fn vote_rematch(&mut self, seat: usize) {
+        if self.game_phase != GamePhase::GameOver {
+            let msg = "Rematches can only be voted for once the game is over.".to_string();
+            self.clients_info[seat].send(ServerPacket::SendMsg { msg: Some(msg) });
+            return;
+        }
+        let client_id = self.clients_info[seat].id;
+        if !self.rematch_votes.contains(&client_id) {
+            self.rematch_votes.push(client_id);
+            let num_humans = self.clients_info.iter().filter(|client| !client.is_bot()).count();
+            let msg = format!("{} wants a rematch ({}/{})", self.clients_info[seat].name, self.rematch_votes.len(), num_humans);
+            self.announce(&msg);
+        }
+        self.start_rematch_if_agreed();
+    }
+
+    /// A rematch starts once every player left has voted for it. Bots are always up for one.
+    fn start_rematch_if_agreed(&mut self) {
+        let agreed = self.clients_info.iter().all(|client| client.is_bot() || self.rematch_votes.contains(&client.id));
+        if self.game_phase != GamePhase::GameOver || self.rematch_votes.is_empty() || !agreed {
+            return;
+        }
+        match self.check_can_start() {
+            Ok(()) => {
+                self.announce("Everyone agreed, starting a rematch!");
+                self.start_game();
+            }
+            Err(e) => self.announce(&format!("No rematch yet: {}", e)),
+        }
+    }
+
+    /// Nothing more can be read from a client that went away or sends garbage. Outside of a game
+    /// they simply leave, in a game they are out.
+    fn client_left(&mut self, seat: usize, reason: io::Error) {
+        bunt::println!("{$yellow}{} has left the game ({}){/$}", self.clients_info[seat].name, reason);
+        let client_id = self.clients_info[seat].id;
+        if self.game_phase != GamePhase::InGame {
+            self.clients_info.remove(seat);
+        }
+        else {
+            self.clients_info[seat].connected = false;
+        }
+        if self.host == Some(client_id) {
+            self.host = None;
+            if let Some(new_seat) = self.clients_info.iter().position(|client| client.connected && !client.is_bot()) {
+                self.pass_host_to(new_seat);
+            }
+        }
+        if self.game_phase != GamePhase::InGame {
+            self.rematch_votes.retain(|&id| id != client_id);
+            // The one holding the rematch back may be the one who left
+            self.start_rematch_if_agreed();
+            return;
+        }
+        let state = self.state.as_mut().unwrap();
+        if state.players[seat].is_active {
+            state.eliminate(self.rules.as_ref(), seat);
+            let msg = format!("{} has left the game", self.clients_info[seat].name);
+            self.announce(&msg);
+            if !self.end_game_if_over() {
+                self.send_game_states();
+            }
+        }
+    }
+
+    /// Passes a chat message on to everyone, the sender included
+    fn chat(&mut self, client_id: ClientId, msg: String) {
+        let Some(msg) = clean_chat_msg(&msg) else {return};
+        let Some(sender) = self.everyone().find(|client| client.id == client_id) else {return};
+        if !sender.chat_limiter.allow(Instant::now()) {
+            sender.send(ServerPacket::SendMsg { msg: Some("You are sending messages too fast, wait a little.".to_string()) });
+            return;
+        }
+        let from = sender.name.clone();
+        println!("[chat] {}: {}", from, msg);
+        for client in self.everyone() {
+            client.send(ServerPacket::SendChat { from: from.clone(), msg: msg.clone() });
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::card::{Card, CardKind, Color, Deck, Hand};
+use crate::deck_definition::DeckDefinition;
+use crate::game::{GameState, TableView, TurnMoveError};
+use super::{RuleSet, RulesKind, verify_matches_top_card, choose_card_instructions, draw_until_color_instructions};
+
+/// Uno Flip: every card has a light and a dark side, a Flip card turns the whole game over.
+/// - A draw card can only be answered with the same draw card, or with a Wild Draw2 on the light side
+/// - A Wild Draw Color can't be answered at all, the player has to draw
+/// - Wilds must be given a color of the side that is in play
+pub struct Flip;
+
+impl RuleSet for Flip {
+    fn kind(&self) -> RulesKind {
+        RulesKind::Flip
+    }
+
+    fn cards_per_deck(&self) -> usize {
+        DeckDefinition::flip_light().total_cards()
+    }
+
+    fn build_deck(&self, num_decks: usize) -> Deck {
+        Deck::with_flip_decks(&DeckDefinition::flip_light(), &DeckDefinition::flip_dark(), num_decks)
+    }
+
+    fn wild_colors(&self, state: &GameState) -> Vec<Color> {
+        if state.dark_side {Color::DARK.to_vec()} else {Color::LIGHT.to_vec()}
+    }
+
+    fn verify_card(&self, table: &TableView, _hand: &Hand, player_card: &Card) -> Result<(), TurnMoveError> {
+        let top_card = &table.top_card;
+        if let Some(color) = table.draw_until_color {
+            return Err(TurnMoveError::MustDrawUntilColor(color));
+        }
+        if table.card_debt > 0 {
+            match (&top_card.kind, &player_card.kind) {
+                (CardKind::Draw1, CardKind::Draw1) | (CardKind::Draw5, CardKind::Draw5) => Ok(()),
+                (CardKind::Draw1 | CardKind::WildDraw2, CardKind::WildDraw2) => Ok(()),
+                _ => Err(TurnMoveError::MustAnswerDrawStack { card_debt: table.card_debt }),
+            }
+        }
+        else {
+            verify_matches_top_card(player_card, top_card)
+        }
+    }
+
+    fn card_points(&self, card: &Card) -> usize {
+        match (&card.kind, card.number) {
+            (CardKind::Number, Some(number)) => number as usize,
+            (CardKind::Draw1, _) => 10,
+            (CardKind::SkipEveryone, _) => 30,
+            (CardKind::Wild, _) => 40,
+            (CardKind::WildDraw2, _) => 50,
+            (CardKind::WildDrawColor, _) => 60,
+            _ => 20,
+        }
+    }
+
+    fn instructions(&self, table: &TableView, hand: &Hand) -> String {
+        if let Some(instructions) = draw_until_color_instructions(table) {
+            return instructions;
+        }
+        match table.card_debt {
+            0 => format!("{} You can also type 'p' to pick up 1 card", choose_card_instructions(hand)),
+            card_debt => format!("{} You can only answer with the same draw card (or a Wild Draw2 on a Draw1) to pass the cards on to the next opponent. \
+                                 You can also type 'p' to pick up {} cards",
+                                 choose_card_instructions(hand), card_debt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::tests::{card, state_with_top};
+
+    #[test]
+    fn light_side() {
+        let state = state_with_top(&Flip, 2, card(CardKind::Number, Color::Red, Some(5)), 0);
+        assert!(Flip.verify_move(&state, &card(CardKind::Number, Color::Blue, Some(5)), None).is_ok());
+        assert!(Flip.verify_move(&state, &card(CardKind::Draw1, Color::Red, None), None).is_ok());
+        assert!(Flip.verify_move(&state, &card(CardKind::Flip, Color::Green, None), None).is_err());
+        assert!(Flip.verify_wild_color(&state, &card(CardKind::Wild, Color::Teal, None)).is_err());
+        assert!(Flip.verify_wild_color(&state, &card(CardKind::Wild, Color::Green, None)).is_ok());
+    }
+
+    #[test]
+    fn debts() {
+        let mut state = state_with_top(&Flip, 2, card(CardKind::Draw5, Color::Pink, None), 5);
+        state.dark_side = true;
+        assert!(Flip.verify_move(&state, &card(CardKind::Draw5, Color::Teal, None), None).is_ok());
+        assert!(Flip.verify_move(&state, &card(CardKind::Number, Color::Pink, Some(2)), None).is_err());
+        state.card_debt = 0;
+        state.draw_until_color = Some(Color::Orange);
+        assert_eq!(Flip.verify_move(&state, &card(CardKind::Number, Color::Orange, Some(2)), None), Err(TurnMoveError::MustDrawUntilColor(Color::Orange)));
+    }
+
+    #[test]
+    fn flip_card_turns_the_game_over() {
+        let mut state = state_with_top(&Flip, 2, card(CardKind::Number, Color::Red, Some(5)), 0);
+        let hand_before = state.players[0].hand.iter().map(|card| card.color).collect::<Vec<Option<Color>>>();
+        Flip.apply_card_effect(&mut state, &card(CardKind::Flip, Color::Red, None));
+        assert!(state.dark_side);
+        assert!(state.players[0].hand.iter().all(|card| card.color.is_none_or(|color| Color::DARK.contains(&color))));
+        Flip.apply_card_effect(&mut state, &card(CardKind::Flip, Color::Pink, None));
+        assert_eq!(hand_before, state.players[0].hand.iter().map(|card| card.color).collect::<Vec<Option<Color>>>());
+    }
+}
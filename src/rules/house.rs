@@ -0,0 +1,76 @@
+use crate::card::{Card, CardKind, Color, Deck, Hand};
+use crate::deck_definition::DeckDefinition;
+use crate::game::{GameState, TableView, TurnMoveError};
+use super::{RuleSet, RulesKind, verify_matches_top_card, choose_card_instructions, draw_until_color_instructions};
+
+/// The classic deck with the rules most people play at home: a Draw2 or a Draw4 can be answered
+/// with another one, making the next player pick up all of them
+pub struct House {
+    pub deck_definition: DeckDefinition,
+}
+
+impl RuleSet for House {
+    fn kind(&self) -> RulesKind {
+        RulesKind::House
+    }
+
+    fn cards_per_deck(&self) -> usize {
+        self.deck_definition.total_cards()
+    }
+
+    fn build_deck(&self, num_decks: usize) -> Deck {
+        Deck::with_decks(&self.deck_definition, num_decks)
+    }
+
+    fn wild_colors(&self, _state: &GameState) -> Vec<Color> {
+        self.deck_definition.colors.clone()
+    }
+
+    fn verify_card(&self, table: &TableView, _hand: &Hand, player_card: &Card) -> Result<(), TurnMoveError> {
+        let top_card = &table.top_card;
+        if let Some(color) = table.draw_until_color {
+            return Err(TurnMoveError::MustDrawUntilColor(color));
+        }
+        if table.card_debt > 0 {
+            match (top_card.color, &top_card.kind, player_card.color, &player_card.kind) {
+                (_, CardKind::Draw2, _, CardKind::Draw2 | CardKind::Draw4) => Ok(()),
+                (t_c, CardKind::Draw4, p_c, CardKind::Draw2) if p_c == t_c => Ok(()),
+                (_, CardKind::Draw4, _, CardKind::Draw4) => Ok(()),
+                _ => Err(TurnMoveError::MustAnswerDrawStack { card_debt: table.card_debt }),
+            }
+        }
+        else {
+            verify_matches_top_card(player_card, top_card)
+        }
+    }
+
+    fn instructions(&self, table: &TableView, hand: &Hand) -> String {
+        if let Some(instructions) = draw_until_color_instructions(table) {
+            return instructions;
+        }
+        match table.card_debt {
+            0 => format!("{} You can also type 'p' to pick up 1 card", choose_card_instructions(hand)),
+            card_debt => format!("{} You can only choose a Draw2 or a Draw4 to make the next opponent pick up {} or {} cards respectively. \
+                                 You can also type 'p' to pick up {} cards",
+                                 choose_card_instructions(hand), card_debt + 2, card_debt + 4, card_debt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::tests::{card, state_with_top};
+
+    #[test]
+    fn stacking() {
+        let rules = House { deck_definition: DeckDefinition::standard() };
+        let state = state_with_top(&rules, 2, card(CardKind::Draw2, Color::Red, None), 2);
+        assert!(rules.verify_move(&state, &card(CardKind::Draw2, Color::Blue, None), None).is_ok());
+        assert!(rules.verify_move(&state, &card(CardKind::Draw4, Color::Blue, None), None).is_ok());
+        assert!(rules.verify_move(&state, &card(CardKind::Number, Color::Red, Some(2)), None).is_err());
+        let state = state_with_top(&rules, 2, card(CardKind::Draw4, Color::Green, None), 4);
+        assert!(rules.verify_move(&state, &card(CardKind::Draw2, Color::Green, None), None).is_ok());
+        assert!(rules.verify_move(&state, &card(CardKind::Draw2, Color::Red, None), None).is_err());
+    }
+}
@@ -0,0 +1,238 @@
+/* Rule sets
+ * - Everything that differs between flavours of Uno (what goes into the deck, which card can go
+ * on which, what a card does, how turns go around and how cards are scored) lives behind the
+ * RuleSet trait. The server picks one implementation at startup and the game engine (GameState)
+ * asks it whenever a rules specific decision has to be made.
+ */
+
+use serde::{Serialize, Deserialize};
+
+use crate::card::{Card, CardKind, Color, Deck, Hand};
+use crate::deck_definition::DeckDefinition;
+use crate::game::{GameState, TableView, TurnMoveError};
+
+pub mod official;
+pub mod house;
+pub mod flip;
+pub mod no_mercy;
+
+/// The rule sets the server can be started with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum RulesKind {
+    /// The official rules: draw cards can't be stacked, a Draw4 is only allowed without a card of the current color
+    Official,
+    /// Draw2 and Draw4 can be stacked to pass the cards on
+    House,
+    /// Uno Flip: double sided cards, a Flip card turns the whole game over to the dark side
+    Flip,
+    /// Uno No Mercy: huge draw cards, 0/7 hand swaps and elimination at 25 cards
+    NoMercy,
+}
+
+impl RulesKind {
+    /// `deck_definition` is used by the rule sets that can be played with a custom deck
+    pub fn rule_set(self, deck_definition: DeckDefinition) -> Box<dyn RuleSet> {
+        match self {
+            RulesKind::Official => Box::new(official::Official { deck_definition }),
+            RulesKind::House => Box::new(house::House { deck_definition }),
+            RulesKind::Flip => Box::new(flip::Flip),
+            RulesKind::NoMercy => Box::new(no_mercy::NoMercy { deck_definition }),
+        }
+    }
+
+    /// The deck a rule set is played with when no deck definition is given
+    pub fn default_deck_definition(self) -> DeckDefinition {
+        match self {
+            RulesKind::NoMercy => DeckDefinition::no_mercy(),
+            _ => DeckDefinition::standard(),
+        }
+    }
+
+    /// Whether the rule set can be played with a deck definition loaded from a file
+    pub fn supports_custom_decks(self) -> bool {
+        self != RulesKind::Flip
+    }
+}
+
+pub trait RuleSet: Send + Sync {
+    fn kind(&self) -> RulesKind;
+
+    // ==== Deck composition ====
+    fn cards_per_deck(&self) -> usize;
+    fn build_deck(&self, num_decks: usize) -> Deck;
+    /// Whether the card may be turned as the first card of the game
+    fn can_start_with(&self, card: &Card) -> bool {
+        !card.kind.is_wild() && card.kind != CardKind::Flip
+    }
+    /// The colors a wild can be given right now
+    fn wild_colors(&self, state: &GameState) -> Vec<Color>;
+
+    // ==== Move validation ====
+    /// Whether the card can go on the table, for a player holding `hand`. Clients run this too, so
+    /// it only looks at what every player can see, and a wild may not have its color yet.
+    fn verify_card(&self, table: &TableView, hand: &Hand, card: &Card) -> Result<(), TurnMoveError>;
+    /// Called after verify_wild_color, so the top card and the player card have a color.
+    fn verify_move(&self, state: &GameState, card: &Card, _target_player: Option<usize>) -> Result<(), TurnMoveError> {
+        self.verify_card(&state.table_view(), state.current_hand(), card)
+    }
+    fn verify_wild_color(&self, state: &GameState, card: &Card) -> Result<(), TurnMoveError> {
+        let colors = self.wild_colors(state);
+        match card.color {
+            None => Err(TurnMoveError::MissingColorChoice),
+            Some(color) if card.kind.is_wild() && !colors.contains(&color) => Err(TurnMoveError::WildColorNotInPlay { colors }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the card is played on another player, who is then sent along with it
+    fn takes_target(&self, _card: &Card) -> bool {
+        false
+    }
+
+    // ==== Card effects ====
+    /// Applies what the card that just went on top of the stack does to the game
+    fn apply_card_effect(&self, state: &mut GameState, card: &Card) {
+        apply_standard_effect(state, card);
+    }
+    /// Effects that move cards between hands. Only applies to cards played by the current player
+    /// (never to the first card of the game), and not if that was their last card.
+    fn apply_hand_effect(&self, _state: &mut GameState, _card: &Card, _target_player: Option<usize>) {}
+    /// The current player picks up instead of playing. Returns whether they keep their turn.
+    fn pick_up(&self, state: &mut GameState) -> bool {
+        pick_up_standard(state);
+        false
+    }
+    /// Whether a player holding this many cards is out of the game
+    fn is_eliminated(&self, _hand_len: usize) -> bool {
+        false
+    }
+
+    // ==== Turn advance ====
+    fn next_player(&self, state: &mut GameState) {
+        state.advance_turn();
+    }
+
+    // ==== Scoring ====
+    /// Points a card left in a hand is worth at the end of the game
+    fn card_points(&self, card: &Card) -> usize {
+        match (&card.kind, card.number) {
+            (CardKind::Number, Some(number)) => number as usize,
+            (CardKind::Draw1, _) => 10,
+            (CardKind::SkipEveryone, _) => 30,
+            (kind, _) if kind.is_wild() => 50,
+            _ => 20,
+        }
+    }
+
+    /// What the player is told they can do on their turn
+    fn instructions(&self, table: &TableView, hand: &Hand) -> String;
+}
+
+/// Whether the player card may go on the top card when there is nothing to pay off
+pub fn matches_top_card(player_card: &Card, top_card: &Card) -> bool {
+    match (top_card.color, player_card.color, &top_card.kind, &player_card.kind, top_card.number, player_card.number) {
+        // If colors are same, can play any kind
+        (Some(t_c), Some(p_c), _, _, _, _) if t_c == p_c => true,
+        // If kinds are same but not numbers
+        (_, _, t_k, p_k, None, None) if t_k == p_k => true,
+        // Wilds can go on anything
+        (_, _, _, p_k, _, _) if p_k.is_wild() => true,
+        // if top card and player card is number kind and numbers are same
+        (_, _, CardKind::Number, CardKind::Number, Some(t_n), Some(p_n)) if t_n == p_n => true,
+        _ => false,
+    }
+}
+
+/// Like matches_top_card, but says why the card doesn't match
+pub fn verify_matches_top_card(player_card: &Card, top_card: &Card) -> Result<(), TurnMoveError> {
+    match (matches_top_card(player_card, top_card), &player_card.kind) {
+        (true, _) => Ok(()),
+        (false, CardKind::Number) => Err(TurnMoveError::WrongColorOrNumber),
+        (false, _) => Err(TurnMoveError::WrongColorOrKind),
+    }
+}
+
+/// What every card does in every rule set, unless the rule set says otherwise
+pub fn apply_standard_effect(state: &mut GameState, card: &Card) {
+    match card.kind {
+        CardKind::Number | CardKind::Wild | CardKind::DiscardAll => {},
+        CardKind::Skip => state.skip_debt = 1,
+        // Everyone else is skipped, so the turn comes back to whoever played it
+        CardKind::SkipEveryone => state.skip_debt = state.active_players() - 1,
+        CardKind::Reverse => state.direction.flip(),
+        CardKind::WildReverseDraw4 => {
+            state.direction.flip();
+            state.card_debt += card.kind.draw_value();
+        }
+        CardKind::Draw1 | CardKind::Draw2 | CardKind::WildDraw2 | CardKind::Draw4 | CardKind::ColoredDraw4 | CardKind::Draw5 | CardKind::WildDraw6 | CardKind::WildDraw10 => {
+            state.card_debt += card.kind.draw_value();
+        }
+        CardKind::WildDrawColor | CardKind::WildColorRoulette => state.draw_until_color = card.color,
+        CardKind::Flip => state.flip(),
+    }
+}
+
+/// Picks up until the color chosen by a Wild Draw Color comes up, or the card debt, or 1 card
+pub fn pick_up_standard(state: &mut GameState) {
+    if let Some(color) = state.draw_until_color.take() {
+        // If every card is in someone's hand there is nothing left to pick up
+        while let Some(card) = state.draw_card() {
+            let is_chosen_color = card.color == Some(color);
+            state.players[state.turn].hand.push(card);
+            if is_chosen_color {break;}
+        }
+    }
+    else {
+        let pick_up_amt = if state.card_debt > 0 {state.card_debt} else {1};
+        state.draw_cards_for_current(pick_up_amt);
+    }
+}
+
+/// The part of the instructions every rule set starts with
+fn choose_card_instructions(hand: &Hand) -> String {
+    format!("Type number 1-{} to choose the card at that index as indicated above in your hand. \
+            If choosing a wild card, type the chosen color as well (eg: `2 blue` given 2 has a Draw4 or a Wild). \
+            Type 's' to sort your hand differently.",
+            hand.len())
+}
+
+/// Instructions for a player who was hit by a Wild Draw Color or a Wild Color Roulette
+fn draw_until_color_instructions(table: &TableView) -> Option<String> {
+    table.draw_until_color.map(|color| {
+        format!("You were hit by a {}! Type 'p' to draw cards until you get a {} card", table.top_card.kind, color)
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::game::Direction;
+
+    pub fn card(kind: CardKind, color: Color, number: Option<u8>) -> Card {
+        Card { id: 0, kind, color: Some(color), number, other_side: None }
+    }
+
+    /// A game where `top` was just played and it is the first player's turn, going up
+    pub fn state_with_top(rules: &dyn RuleSet, num_players: usize, top: Card, card_debt: usize) -> GameState {
+        let mut state = GameState::new(rules, num_players, 1);
+        state.stack.push_front(top);
+        state.card_debt = card_debt;
+        state.skip_debt = 0;
+        state.draw_until_color = None;
+        state.direction = Direction::Positive;
+        state.turn = 0;
+        state
+    }
+
+    #[test]
+    fn matching_top_card() {
+        let top = card(CardKind::Number, Color::Red, Some(5));
+        assert!(matches_top_card(&card(CardKind::Number, Color::Red, Some(1)), &top));
+        assert!(matches_top_card(&card(CardKind::Number, Color::Blue, Some(5)), &top));
+        assert!(matches_top_card(&card(CardKind::Wild, Color::Blue, None), &top));
+        assert!(!matches_top_card(&card(CardKind::Skip, Color::Blue, None), &top));
+        assert!(matches_top_card(&card(CardKind::Skip, Color::Blue, None), &card(CardKind::Skip, Color::Green, None)));
+        assert_eq!(verify_matches_top_card(&card(CardKind::Number, Color::Blue, Some(1)), &top), Err(TurnMoveError::WrongColorOrNumber));
+        assert_eq!(verify_matches_top_card(&card(CardKind::Skip, Color::Blue, None), &top), Err(TurnMoveError::WrongColorOrKind));
+    }
+}
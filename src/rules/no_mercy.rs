@@ -0,0 +1,250 @@
+use crate::card::{Card, CardKind, Color, Deck, Hand};
+use crate::deck_definition::DeckDefinition;
+use crate::game::{GameState, Direction, TableView, TurnMoveError};
+use super::{RuleSet, RulesKind, verify_matches_top_card, pick_up_standard, choose_card_instructions, draw_until_color_instructions};
+
+/// In No Mercy, a player holding this many cards is out of the game
+pub const MERCY_LIMIT : usize = 25;
+
+/// Uno No Mercy
+/// - Any draw card can be stacked on a draw card worth the same or less
+/// - A Wild Color Roulette can't be answered, the player has to draw until the chosen color comes up
+/// - Discard All, 0 (pass every hand along) and 7 (swap hands) move cards between hands
+/// - A player who can't play draws until they can, and is out once they hold MERCY_LIMIT cards
+pub struct NoMercy {
+    pub deck_definition: DeckDefinition,
+}
+
+impl RuleSet for NoMercy {
+    fn kind(&self) -> RulesKind {
+        RulesKind::NoMercy
+    }
+
+    fn cards_per_deck(&self) -> usize {
+        self.deck_definition.total_cards()
+    }
+
+    fn build_deck(&self, num_decks: usize) -> Deck {
+        Deck::with_decks(&self.deck_definition, num_decks)
+    }
+
+    fn wild_colors(&self, _state: &GameState) -> Vec<Color> {
+        self.deck_definition.colors.clone()
+    }
+
+    fn verify_card(&self, table: &TableView, _hand: &Hand, player_card: &Card) -> Result<(), TurnMoveError> {
+        let top_card = &table.top_card;
+        if let Some(color) = table.draw_until_color {
+            return Err(TurnMoveError::MustDrawUntilColor(color));
+        }
+        if table.card_debt > 0 {
+            match (top_card.kind.draw_value(), player_card.kind.draw_value()) {
+                (_, 0) => Err(TurnMoveError::MustAnswerDrawStack { card_debt: table.card_debt }),
+                (t_v, p_v) if p_v >= t_v => Ok(()),
+                (t_v, _) => Err(TurnMoveError::DrawCardTooWeak { min_value: t_v }),
+            }
+        }
+        else {
+            verify_matches_top_card(player_card, top_card)
+        }
+    }
+
+    fn verify_move(&self, state: &GameState, player_card: &Card, target_player: Option<usize>) -> Result<(), TurnMoveError> {
+        self.verify_card(&state.table_view(), state.current_hand(), player_card)?;
+        // A 7 has to name an active player (other than whoever plays it) to swap hands with
+        if !self.takes_target(player_card) {
+            return Ok(());
+        }
+        match target_player {
+            None => Err(TurnMoveError::MissingSwapTarget),
+            Some(target) if target == state.turn => Err(TurnMoveError::SwapWithSelf),
+            Some(target) if state.players.get(target).is_some_and(|player| player.is_active) => Ok(()),
+            Some(_) => Err(TurnMoveError::InvalidTarget),
+        }
+    }
+
+    fn takes_target(&self, card: &Card) -> bool {
+        card.kind == CardKind::Number && card.number == Some(7)
+    }
+
+    fn apply_hand_effect(&self, state: &mut GameState, card: &Card, target_player: Option<usize>) {
+        let curr_player = state.turn;
+        match (&card.kind, card.number) {
+            // Every other card of that color goes as well, right under the Discard All
+            (CardKind::DiscardAll, _) => {
+                let color = card.color;
+                let discarded = state.players[curr_player].hand.remove_where(|card| card.color == color);
+                for discarded_card in discarded {
+                    state.stack.insert(1, discarded_card);
+                }
+            }
+            // Everyone passes their hand to the next player in the direction of play
+            (CardKind::Number, Some(0)) => {
+                let mut active_ids = (0..state.players.len()).filter(|&idx| state.players[idx].is_active).collect::<Vec<usize>>();
+                if let Direction::Negative = state.direction {
+                    active_ids.reverse();
+                }
+                let mut passed_hand = state.players[*active_ids.last().unwrap()].hand.clone();
+                for &idx in &active_ids {
+                    passed_hand = std::mem::replace(&mut state.players[idx].hand, passed_hand);
+                }
+            }
+            (CardKind::Number, Some(7)) => {
+                let target = target_player.unwrap();
+                let target_hand = std::mem::replace(&mut state.players[target].hand, Hand::empty());
+                let own_hand = std::mem::replace(&mut state.players[curr_player].hand, target_hand);
+                state.players[target].hand = own_hand;
+            }
+            _ => {},
+        }
+    }
+
+    fn pick_up(&self, state: &mut GameState) -> bool {
+        // The card that was drawn is played, or the turn is over
+        if state.drew_playable {
+            return false;
+        }
+        if state.card_debt > 0 || state.draw_until_color.is_some() {
+            pick_up_standard(state);
+            return false;
+        }
+        // Keep drawing until there is something to play, then play it
+        while let Some(card) = state.draw_card() {
+            let is_playable = self.verify_card(&state.table_view(), state.current_hand(), &card).is_ok();
+            state.players[state.turn].hand.push(card);
+            if is_playable || self.is_eliminated(state.current_hand().len()) {
+                state.drew_playable = is_playable;
+                return is_playable;
+            }
+        }
+        false
+    }
+
+    fn is_eliminated(&self, hand_len: usize) -> bool {
+        hand_len >= MERCY_LIMIT
+    }
+
+    fn instructions(&self, table: &TableView, hand: &Hand) -> String {
+        if let Some(instructions) = draw_until_color_instructions(table) {
+            return instructions;
+        }
+        match table.card_debt {
+            0 => format!("{} If choosing a 7, type the number of the player to swap hands with as well (eg: `3 2`). \
+                         You can also type 'p' to draw until you get a card you can play. Careful, {} cards and you are out!",
+                         choose_card_instructions(hand), MERCY_LIMIT),
+            card_debt => format!("{} You can stack any draw card worth at least as much as the last one to pass the cards on to the next opponent. \
+                                 You can also type 'p' to pick up {} cards. Careful, {} cards and you are out!",
+                                 choose_card_instructions(hand), card_debt, MERCY_LIMIT),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::tests::{card, state_with_top};
+    use crate::game::MoveOutcome;
+
+    fn rules() -> NoMercy {
+        NoMercy { deck_definition: DeckDefinition::no_mercy() }
+    }
+
+    #[test]
+    fn stacking() {
+        let state = state_with_top(&rules(), 3, card(CardKind::WildDraw6, Color::Red, None), 6);
+        assert!(rules().verify_move(&state, &card(CardKind::WildDraw10, Color::Blue, None), None).is_ok());
+        assert!(rules().verify_move(&state, &card(CardKind::WildDraw6, Color::Blue, None), None).is_ok());
+        assert_eq!(rules().verify_move(&state, &card(CardKind::WildReverseDraw4, Color::Red, None), None), Err(TurnMoveError::DrawCardTooWeak { min_value: 6 }));
+        assert!(rules().verify_move(&state, &card(CardKind::Number, Color::Red, Some(1)), None).is_err());
+        let state = state_with_top(&rules(), 3, card(CardKind::Draw2, Color::Red, None), 2);
+        assert!(rules().verify_move(&state, &card(CardKind::ColoredDraw4, Color::Blue, None), None).is_ok());
+    }
+
+    #[test]
+    fn seven_swaps_hands() {
+        let mut state = state_with_top(&rules(), 3, card(CardKind::Number, Color::Red, Some(3)), 0);
+        let seven = card(CardKind::Number, Color::Red, Some(7));
+        assert_eq!(rules().verify_move(&state, &seven, None), Err(TurnMoveError::MissingSwapTarget));
+        assert_eq!(rules().verify_move(&state, &seven, Some(0)), Err(TurnMoveError::SwapWithSelf));
+        assert!(rules().verify_move(&state, &seven, Some(2)).is_ok());
+        state.players[2].hand.push(seven.clone());
+        let (own_len, target_len) = (state.players[0].hand.len(), state.players[2].hand.len());
+        rules().apply_hand_effect(&mut state, &seven, Some(2));
+        assert_eq!((state.players[0].hand.len(), state.players[2].hand.len()), (target_len, own_len));
+    }
+
+    #[test]
+    fn zero_passes_hands_along() {
+        let mut state = state_with_top(&rules(), 3, card(CardKind::Number, Color::Red, Some(3)), 0);
+        state.players[1].hand.push(card(CardKind::Number, Color::Red, Some(1)));
+        let lens = state.players.iter().map(|player| player.hand.len()).collect::<Vec<usize>>();
+        rules().apply_hand_effect(&mut state, &card(CardKind::Number, Color::Red, Some(0)), None);
+        assert_eq!(state.players[2].hand.len(), lens[1]);
+        assert_eq!(state.players[0].hand.len(), lens[2]);
+        assert_eq!(state.players[1].hand.len(), lens[0]);
+    }
+
+    #[test]
+    fn discard_all() {
+        let mut state = state_with_top(&rules(), 3, card(CardKind::Number, Color::Red, Some(3)), 0);
+        state.players[0].hand.push(card(CardKind::Number, Color::Red, Some(1)));
+        rules().apply_hand_effect(&mut state, &card(CardKind::DiscardAll, Color::Red, None), None);
+        assert!(state.players[0].hand.iter().all(|card| card.color != Some(Color::Red)));
+    }
+
+    #[test]
+    fn discarding_the_last_cards_wins() {
+        let mut state = state_with_top(&rules(), 3, card(CardKind::Number, Color::Red, Some(3)), 0);
+        state.players[0].hand = Hand::empty();
+        state.players[0].hand.push(Card { id: 1000, ..card(CardKind::DiscardAll, Color::Red, None) });
+        state.players[0].hand.push(Card { id: 1001, ..card(CardKind::Number, Color::Red, Some(1)) });
+        assert_eq!(state.play_card(&rules(), 1000, None, None), Ok((MoveOutcome::Won, vec![])));
+        assert!(!state.players[0].is_active);
+    }
+
+    #[test]
+    fn hands_passed_over_the_mercy_limit() {
+        let mut state = state_with_top(&rules(), 3, card(CardKind::Number, Color::Red, Some(3)), 0);
+        for id in 0..MERCY_LIMIT as u32 {
+            state.players[2].hand.push(Card { id: 2000 + id, ..card(CardKind::Number, Color::Blue, Some(1)) });
+        }
+        // A 0 passes the big hand on to the player who played it
+        state.players[0].hand.push(Card { id: 1000, ..card(CardKind::Number, Color::Red, Some(0)) });
+        assert_eq!(state.play_card(&rules(), 1000, None, None), Ok((MoveOutcome::Eliminated, vec![])));
+        assert!(state.players[0].is_eliminated);
+        // A 7 swaps it onto someone else
+        let mut state = state_with_top(&rules(), 3, card(CardKind::Number, Color::Red, Some(3)), 0);
+        for id in 0..MERCY_LIMIT as u32 {
+            state.players[0].hand.push(Card { id: 2000 + id, ..card(CardKind::Number, Color::Blue, Some(1)) });
+        }
+        state.players[0].hand.push(Card { id: 1000, ..card(CardKind::Number, Color::Red, Some(7)) });
+        assert_eq!(state.play_card(&rules(), 1000, None, Some(1)), Ok((MoveOutcome::Continue, vec![1])));
+        assert!(state.players[1].is_eliminated);
+        assert!(state.players[0].is_active);
+        assert_eq!(state.turn, 2);
+    }
+
+    #[test]
+    fn drawing_until_playable() {
+        let mut state = state_with_top(&rules(), 3, card(CardKind::Number, Color::Red, Some(3)), 0);
+        state.players[0].hand = Hand::empty();
+        // Only red 1s left to draw
+        state.deck = Deck::from_definition(&toml::from_str("colors = [\"Red\"]\nnumbers = [0, 2, 0, 0, 0, 0, 0, 0, 0, 0]").unwrap());
+        assert_eq!(state.pick(&rules()), MoveOutcome::Continue);
+        assert_eq!((state.turn, state.players[0].hand.len()), (0, 1));
+        assert!(state.drew_playable);
+        // Picking up again passes rather than drawing more
+        state.pick(&rules());
+        assert_eq!((state.turn, state.players[0].hand.len()), (1, 1));
+        assert!(!state.drew_playable);
+    }
+
+    #[test]
+    fn mercy_rule() {
+        let mut state = state_with_top(&rules(), 3, card(CardKind::WildDraw10, Color::Red, None), 30);
+        assert_eq!(state.pick(&rules()), MoveOutcome::Eliminated);
+        assert!(state.players[0].is_eliminated);
+        assert!(state.players[0].hand.is_empty());
+        assert_eq!(state.turn, 1);
+    }
+}
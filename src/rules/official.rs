@@ -0,0 +1,106 @@
+use crate::card::{Card, CardKind, Color, Deck, Hand};
+use crate::deck_definition::DeckDefinition;
+use crate::game::{GameState, TableView, TurnMoveError};
+use super::{RuleSet, RulesKind, verify_matches_top_card, apply_standard_effect, choose_card_instructions, draw_until_color_instructions};
+
+/// The rules as printed in the box: draw cards can't be stacked, a Wild Draw4 may only be played
+/// when the player holds no card of the current color, and with two players a Reverse acts like a Skip
+pub struct Official {
+    pub deck_definition: DeckDefinition,
+}
+
+impl RuleSet for Official {
+    fn kind(&self) -> RulesKind {
+        RulesKind::Official
+    }
+
+    fn cards_per_deck(&self) -> usize {
+        self.deck_definition.total_cards()
+    }
+
+    fn build_deck(&self, num_decks: usize) -> Deck {
+        Deck::with_decks(&self.deck_definition, num_decks)
+    }
+
+    fn wild_colors(&self, _state: &GameState) -> Vec<Color> {
+        self.deck_definition.colors.clone()
+    }
+
+    fn verify_card(&self, table: &TableView, hand: &Hand, player_card: &Card) -> Result<(), TurnMoveError> {
+        let top_card = &table.top_card;
+        if let Some(color) = table.draw_until_color {
+            return Err(TurnMoveError::MustDrawUntilColor(color));
+        }
+        if table.card_debt > 0 {
+            return Err(TurnMoveError::MustPickUp { card_debt: table.card_debt });
+        }
+        verify_matches_top_card(player_card, top_card)?;
+        if player_card.kind == CardKind::Draw4 && hand.iter().any(|card| card.color.is_some() && card.color == top_card.color) {
+            return Err(TurnMoveError::Draw4WithMatchingColor);
+        }
+        Ok(())
+    }
+
+    fn apply_card_effect(&self, state: &mut GameState, card: &Card) {
+        apply_standard_effect(state, card);
+        if card.kind == CardKind::Reverse && state.active_players() == 2 {
+            state.skip_debt = 1;
+        }
+    }
+
+    fn instructions(&self, table: &TableView, hand: &Hand) -> String {
+        if let Some(instructions) = draw_until_color_instructions(table) {
+            return instructions;
+        }
+        match table.card_debt {
+            0 => format!("{} You can also type 'p' to pick up 1 card", choose_card_instructions(hand)),
+            card_debt => format!("You have to pick up {} cards, type 'p'", card_debt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::tests::{card, state_with_top};
+    use crate::card::Hand;
+
+    fn rules() -> Official {
+        Official { deck_definition: DeckDefinition::standard() }
+    }
+
+    #[test]
+    fn no_stacking() {
+        let state = state_with_top(&rules(), 2, card(CardKind::Draw2, Color::Red, None), 2);
+        assert!(rules().verify_move(&state, &card(CardKind::Draw2, Color::Blue, None), None).is_err());
+    }
+
+    #[test]
+    fn draw4_needs_no_matching_color() {
+        let mut state = state_with_top(&rules(), 2, card(CardKind::Number, Color::Red, Some(3)), 0);
+        state.players[state.turn].hand = Hand::empty();
+        state.players[state.turn].hand.push(card(CardKind::Number, Color::Red, Some(1)));
+        assert!(rules().verify_move(&state, &card(CardKind::Draw4, Color::Blue, None), None).is_err());
+        state.players[state.turn].hand = Hand::empty();
+        state.players[state.turn].hand.push(card(CardKind::Number, Color::Green, Some(1)));
+        assert!(rules().verify_move(&state, &card(CardKind::Draw4, Color::Blue, None), None).is_ok());
+    }
+
+    #[test]
+    fn client_side_check() {
+        let state = state_with_top(&rules(), 2, card(CardKind::Number, Color::Red, Some(3)), 0);
+        let table = state.table_view();
+        let hand = Hand::empty();
+        let wild = Card { id: 0, kind: CardKind::Wild, color: None, number: None, other_side: None };
+        assert!(rules().verify_card(&table, &hand, &wild).is_ok());
+        assert!(rules().verify_card(&table, &hand, &card(CardKind::Number, Color::Blue, Some(3))).is_ok());
+        assert!(rules().verify_card(&table, &hand, &card(CardKind::Skip, Color::Blue, None)).is_err());
+    }
+
+    #[test]
+    fn reverse_skips_with_two_players() {
+        let mut state = state_with_top(&rules(), 2, card(CardKind::Number, Color::Red, Some(3)), 0);
+        rules().apply_card_effect(&mut state, &card(CardKind::Reverse, Color::Red, None));
+        assert_eq!(state.skip_debt, 1);
+    }
+}
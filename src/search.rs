@@ -0,0 +1,244 @@
+/* Search
+ * - The expert bot: Monte Carlo tree search over the moves of bot.rs. The cards the bot can't
+ * see (the deck and the other hands) are dealt again at random for every iteration, keeping the
+ * size of every hand and the discard pile. The tree is walked down among the moves that are
+ * legal in that deal, and the game is then played out with greedy moves.
+ * - Runs for as long as it is given, so that the table never waits on it for long.
+ * - `runo-game --analyze` runs it on a game saved by the server's `save` command.
+ */
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use rand::{thread_rng, seq::SliceRandom};
+
+use crate::bot::{self, Move};
+use crate::game::GameState;
+use crate::netcode::console::SavedServer;
+use crate::netcode::room::GamePhase;
+use crate::rules::RuleSet;
+
+/// Weighs trying the moves that were tried the least against those that did well
+const EXPLORATION : f64 = 0.7;
+/// Games are played out for at most this many moves, and scored on the cards held by then
+const MAX_PLAYOUT_MOVES : usize = 200;
+
+struct Node {
+    /// The seat that makes the move leading here, and the move. None for the root
+    action: Option<(usize, Move)>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    visits: u32,
+    /// Sum of what the iterations through here were worth to the seat making the move
+    reward: f64,
+    /// Number of iterations in which the move could be made, as it depends on the deal
+    available: u32,
+}
+
+impl Node {
+    fn new(action: Option<(usize, Move)>, parent: Option<usize>) -> Node {
+        Node { action, parent, children: vec![], visits: 0, reward: 0.0, available: 1 }
+    }
+}
+
+/// How a move of the player to move did in the search
+pub struct MoveStats {
+    pub candidate: Move,
+    pub visits: u32,
+    pub win_rate: f64,
+}
+
+pub struct SearchResult {
+    /// The move tried the most
+    pub best: Move,
+    /// Every move tried, the best first
+    pub moves: Vec<MoveStats>,
+    pub iterations: usize,
+}
+
+/// Looks for the best move of the player whose turn it is for about `budget`
+pub fn search(rules: &dyn RuleSet, state: &GameState, budget: Duration) -> SearchResult {
+    let me = state.turn;
+    let deadline = Instant::now() + budget;
+    let mut tree = vec![Node::new(None, None)];
+    let mut iterations = 0;
+    let mut rng = thread_rng();
+    // At least one iteration, so that there is a move to make however short the budget
+    while iterations == 0 || Instant::now() < deadline {
+        iterations += 1;
+        let mut state = determinize(state, me);
+        let mut node = 0;
+        while !is_over(&state, me) {
+            let seat = state.turn;
+            let moves = bot::legal_moves(rules, &state);
+            let children = tree[node].children.iter().copied()
+                .filter(|&child| tree[child].action.is_some_and(|(child_seat, child_move)| child_seat == seat && moves.contains(&child_move)))
+                .collect::<Vec<usize>>();
+            children.iter().for_each(|&child| tree[child].available += 1);
+            let untried = moves.iter().filter(|&&candidate| !children.iter().any(|&child| tree[child].action == Some((seat, candidate)))).collect::<Vec<&Move>>();
+            if let Some(&&candidate) = untried.choose(&mut rng) {
+                let child = tree.len();
+                tree.push(Node::new(Some((seat, candidate)), Some(node)));
+                tree[node].children.push(child);
+                apply(rules, &mut state, candidate);
+                node = child;
+                break;
+            }
+            let ucb = |child: usize| {
+                let child = &tree[child];
+                let visits = child.visits.max(1) as f64;
+                child.reward / visits + EXPLORATION * ((child.available as f64).ln() / visits).sqrt()
+            };
+            node = children.into_iter().max_by(|&a, &b| ucb(a).total_cmp(&ucb(b))).unwrap();
+            apply(rules, &mut state, tree[node].action.unwrap().1);
+        }
+        play_out(rules, &mut state, me);
+        let rewards = rewards(&state);
+        let mut current = Some(node);
+        while let Some(idx) = current {
+            let node = &mut tree[idx];
+            node.visits += 1;
+            if let Some((seat, _)) = node.action {
+                node.reward += rewards[seat];
+            }
+            current = node.parent;
+        }
+    }
+    let mut moves = tree[0].children.iter().map(|&child| {
+        let child = &tree[child];
+        MoveStats { candidate: child.action.unwrap().1, visits: child.visits, win_rate: child.reward / child.visits.max(1) as f64 }
+    }).collect::<Vec<MoveStats>>();
+    moves.sort_by_key(|stats| std::cmp::Reverse(stats.visits));
+    let best = moves.first().map_or(Move::Pick, |stats| stats.candidate);
+    SearchResult { best, moves, iterations }
+}
+
+pub fn search_move(rules: &dyn RuleSet, state: &GameState, budget: Duration) -> Move {
+    search(rules, state, budget).best
+}
+
+/// A copy of the game where the cards `me` can't see are dealt again at random
+fn determinize(state: &GameState, me: usize) -> GameState {
+    let mut state = state.clone();
+    let others = (0..state.players.len()).filter(|&player| player != me && state.players[player].is_active).collect::<Vec<usize>>();
+    let sizes = others.iter().map(|&player| state.players[player].hand.len()).collect::<Vec<usize>>();
+    for &player in &others {
+        let cards = state.players[player].hand.take_all();
+        state.deck.refill(cards);
+    }
+    for (&player, &size) in others.iter().zip(&sizes) {
+        for _ in 0..size {
+            let card = state.deck.pop_random_card();
+            state.players[player].hand.push(card);
+        }
+    }
+    state
+}
+
+/// The search stops once `me` is out, one way or the other
+fn is_over(state: &GameState, me: usize) -> bool {
+    !state.players[me].is_active || state.active_players() < 2
+}
+
+fn apply(rules: &dyn RuleSet, state: &mut GameState, candidate: Move) {
+    match candidate {
+        Move::Card { card_id, color_choice, target_player } => {
+            if state.play_card(rules, card_id, color_choice, target_player).is_err() {
+                state.pick(rules);
+            }
+        }
+        Move::Pick => {
+            state.pick(rules);
+        }
+    }
+}
+
+fn play_out(rules: &dyn RuleSet, state: &mut GameState, me: usize) {
+    for _ in 0..MAX_PLAYOUT_MOVES {
+        if is_over(state, me) {
+            break;
+        }
+        let candidate = bot::greedy_move(rules, state);
+        apply(rules, state, candidate);
+    }
+}
+
+/// What the end of a play out is worth to every seat: 1 for going out, 0 for being out or left
+/// last, and up to 0.5 for holding fewer cards than the others when it was cut short
+fn rewards(state: &GameState) -> Vec<f64> {
+    state.players.iter().enumerate().map(|(seat, player)| {
+        if !player.is_active {
+            return if player.is_eliminated {0.0} else {1.0};
+        }
+        let others = state.players.iter().enumerate().filter(|&(other, other_player)| other != seat && other_player.is_active).map(|(_, other_player)| other_player.hand.len()).collect::<Vec<usize>>();
+        if others.is_empty() {
+            return 0.0;
+        }
+        let beaten = others.iter().map(|&cards| match cards.cmp(&player.hand.len()) {
+            std::cmp::Ordering::Greater => 1.0,
+            std::cmp::Ordering::Equal => 0.5,
+            std::cmp::Ordering::Less => 0.0,
+        }).sum::<f64>();
+        0.5 * beaten / others.len() as f64
+    }).collect()
+}
+
+/// Prints what the search makes of the player whose turn it is, in a room of a saved game
+pub fn analyze(path: &Path, room: &str, budget: Duration) -> Result<(), String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    let saved = serde_json::from_str::<SavedServer>(&json).map_err(|e| format!("{} is not a saved game: {}", path.display(), e))?;
+    let saved_room = saved.rooms.into_iter().find(|saved_room| saved_room.name == room).ok_or(format!("There is no room called {} in the save", room))?;
+    let Some(state) = saved_room.state.filter(|_| saved_room.game_phase == GamePhase::InGame) else {
+        return Err(format!("No game was being played in {}", room));
+    };
+    let rules = saved_room.rules.rule_set(saved_room.rules.default_deck_definition());
+    let player = saved_room.players.get(state.turn).cloned().unwrap_or(format!("Player {}", state.turn + 1));
+    let hand = state.current_hand().iter().map(|card| card.get_colorized_repr()).collect::<Vec<String>>().join(", ");
+    bunt::println!("{$bold}{}{/$} to play on {}, holding {}", player, state.top_card().get_colorized_repr(), hand);
+    let started = Instant::now();
+    let result = search(rules.as_ref(), &state, budget);
+    println!("{} games played out in {:.1}s\n", result.iterations, started.elapsed().as_secs_f64());
+    println!("  {:<32} {:>8} {:>8}", "Move", "Tried", "Won");
+    for stats in result.moves.iter() {
+        let description = match stats.candidate {
+            Move::Pick => "Pick up".to_string(),
+            Move::Card { card_id, color_choice, target_player } => {
+                let card = state.current_hand().get(card_id).map_or("?".to_string(), |card| card.to_string());
+                let color = color_choice.map_or(String::new(), |color| format!(" ({:?})", color));
+                let target = target_player.map_or(String::new(), |target| format!(" on {}", saved_room.players.get(target).cloned().unwrap_or_default()));
+                format!("{}{}{}", card, color, target)
+            }
+        };
+        println!("  {:<32} {:>8} {:>7.0}%", description, stats.visits, stats.win_rate * 100.0);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::{Card, CardKind, Color, Hand};
+    use crate::deck_definition::DeckDefinition;
+    use crate::rules::RulesKind;
+
+    #[test]
+    fn determinized_search() {
+        let rules = RulesKind::Official.rule_set(DeckDefinition::standard());
+        let mut state = GameState::new(rules.as_ref(), 3, 1);
+        let me = state.turn;
+        let deal = determinize(&state, me);
+        // Only the hands of the others are dealt again
+        assert_eq!(deal.players[me].hand.iter().map(|card| card.id).collect::<Vec<_>>(), state.players[me].hand.iter().map(|card| card.id).collect::<Vec<_>>());
+        assert!(deal.players.iter().zip(&state.players).all(|(dealt, player)| dealt.hand.len() == player.hand.len()));
+        assert_eq!(deal.deck.len(), state.deck.len());
+        // With a single card that can be played, going out is the only sensible move
+        state.stack.push_front(Card { id: 1000, kind: CardKind::Number, color: Some(Color::Red), number: Some(5), other_side: None });
+        state.card_debt = 0;
+        state.draw_until_color = None;
+        state.players[me].hand = Hand::empty();
+        state.players[me].hand.push(Card { id: 1001, kind: CardKind::Number, color: Some(Color::Red), number: Some(2), other_side: None });
+        let result = search(rules.as_ref(), &state, Duration::from_millis(50));
+        assert_eq!(result.best, Move::Card { card_id: 1001, color_choice: None, target_player: None });
+        assert!(result.moves[0].win_rate > 0.99);
+    }
+}
@@ -0,0 +1,137 @@
+/* Theme
+ * - How card colors look on screen: the palette, markers for players who can't rely on color,
+ * and whether colors are used at all (the NO_COLOR convention, see https://no-color.org).
+ * - Chosen once when the client starts and read from everywhere cards are printed, including
+ * Display, which is why it is kept in a global rather than passed around.
+ */
+
+use std::sync::OnceLock;
+
+use crate::card::Color;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Palette {
+    /// The terminal's own red, green, blue and yellow
+    #[default]
+    Standard,
+    /// Bright, fully saturated colors that stand out on a dark background
+    HighContrast,
+    /// The Okabe-Ito colors, which stay apart for the common kinds of color blindness
+    OkabeIto,
+}
+
+/// Added to colored cards so that the color can be told without seeing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Markers {
+    #[default]
+    None,
+    /// R, G, B, Y, and Pi, T, O, Pu on the dark side of Uno Flip
+    Letters,
+    /// A different shape for every color
+    Glyphs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub palette: Palette,
+    pub markers: Markers,
+    pub use_color: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme { palette: Palette::Standard, markers: Markers::None, use_color: true }
+    }
+}
+
+static THEME : OnceLock<Theme> = OnceLock::new();
+
+impl Theme {
+    /// Colors are turned off when NO_COLOR is set to anything. Without colors, cards always get
+    /// letter markers since nothing else would tell them apart.
+    pub fn new(palette: Palette, markers: Markers) -> Theme {
+        let use_color = std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty());
+        let markers = match (use_color, markers) {
+            (false, Markers::None) => Markers::Letters,
+            (_, markers) => markers,
+        };
+        Theme { palette, markers, use_color }
+    }
+
+    /// Only the first call has an effect
+    pub fn install(self) {
+        let _ = THEME.set(self);
+    }
+
+    /// The installed theme, or the default one (the server never installs one)
+    pub fn current() -> Theme {
+        THEME.get().copied().unwrap_or_default()
+    }
+
+    pub fn rgb(&self, color: Option<Color>) -> (u8, u8, u8) {
+        use Color::*;
+        match (self.palette, color) {
+            // Wilds in the hand have no color yet
+            (_, None) => (0, 200, 200),
+            (Palette::Standard, Some(color)) => match color {
+                Red => (230, 40, 40), Green => (40, 170, 60), Blue => (50, 100, 230), Yellow => (240, 200, 0),
+                Pink => (255, 105, 180), Teal => (0, 170, 170), Orange => (255, 140, 0), Purple => (160, 80, 220),
+            },
+            (Palette::HighContrast, Some(color)) => match color {
+                Red => (255, 40, 40), Green => (0, 255, 0), Blue => (80, 160, 255), Yellow => (255, 255, 0),
+                Pink => (255, 0, 255), Teal => (0, 255, 255), Orange => (255, 165, 0), Purple => (190, 120, 255),
+            },
+            (Palette::OkabeIto, Some(color)) => match color {
+                Red => (213, 94, 0), Green => (0, 158, 115), Blue => (0, 114, 178), Yellow => (240, 228, 66),
+                Pink => (204, 121, 167), Teal => (86, 180, 233), Orange => (230, 159, 0), Purple => (150, 120, 220),
+            },
+        }
+    }
+
+    /// Closest color of the 6x6x6 cube of 256-color terminals
+    pub fn ansi256(&self, color: Option<Color>) -> u8 {
+        let (r, g, b) = self.rgb(color);
+        let level = |value: u8| ((value as u16 * 5 + 127) / 255) as u8;
+        16 + 36 * level(r) + 6 * level(g) + level(b)
+    }
+
+    pub fn marker(&self, color: Color) -> Option<&'static str> {
+        use Color::*;
+        match self.markers {
+            Markers::None => None,
+            Markers::Letters => Some(match color {
+                Red => "R", Green => "G", Blue => "B", Yellow => "Y",
+                Pink => "Pi", Teal => "T", Orange => "O", Purple => "Pu",
+            }),
+            Markers::Glyphs => Some(match color {
+                Red => "●", Green => "▲", Blue => "■", Yellow => "★",
+                Pink => "♥", Teal => "◆", Orange => "✚", Purple => "♣",
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markers_tell_colors_apart() {
+        for markers in [Markers::Letters, Markers::Glyphs] {
+            let theme = Theme { markers, ..Theme::default() };
+            let mut all = Color::LIGHT.iter().chain(Color::DARK.iter()).map(|&color| theme.marker(color).unwrap()).collect::<Vec<&str>>();
+            all.sort();
+            all.dedup();
+            assert_eq!(all.len(), 8);
+        }
+        assert_eq!(Theme::default().marker(Color::Red), None);
+    }
+
+    #[test]
+    fn ansi256_cube() {
+        let theme = Theme { palette: Palette::HighContrast, ..Theme::default() };
+        // Pure yellow and cyan sit in corners of the cube
+        assert_eq!(theme.ansi256(Some(Color::Yellow)), 226);
+        assert_eq!(theme.ansi256(Some(Color::Teal)), 51);
+    }
+}
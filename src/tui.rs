@@ -0,0 +1,472 @@
+/* Full-screen client
+ * - A thread reads packets from the server and hands them to the UI through a channel, so the
+ * screen keeps responding to keys while the other players take their turns.
+ * - Everything shown is built from the last GameSnapshot the server sent.
+ */
+
+use std::{io, net::TcpStream, sync::mpsc, thread, time::{Duration, Instant}};
+
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color as TuiColor, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Clear, List, ListItem, ListState, Paragraph, Wrap},
+};
+
+use crate::card::{Card, CardId, Color};
+use crate::game::{Direction, GameSnapshot};
+use crate::netcode::client_server::ClientConfig;
+use crate::netcode::misc::{parse_host_command, MAX_CHAT_LEN};
+use crate::netcode::packets::{read_packet, send_packet, ClientPacket, ServerPacket};
+use crate::rules::{RuleSet, RulesKind};
+use crate::theme::Theme;
+
+/// How many lines of the log are kept around
+const MAX_LOG_LINES : usize = 200;
+
+/// How long to wait for a key before checking for packets again
+const POLL_INTERVAL : Duration = Duration::from_millis(50);
+
+enum Popup {
+    /// Choosing the color of a wild
+    Color {card_id: CardId, selected: usize},
+    /// Choosing the player a card is played on (eg: a 7 in No Mercy)
+    Target {card_id: CardId, selected: usize},
+}
+
+struct App {
+    name: String,
+    config: ClientConfig,
+    snapshot: Option<GameSnapshot>,
+    /// Built from the rules in the snapshot, to tell which cards can be played
+    rule_set: Option<Box<dyn RuleSet>>,
+    /// Index into the (sorted) hand
+    selected: usize,
+    popup: Option<Popup>,
+    log: Vec<Line<'static>>,
+    chat: Vec<Line<'static>>,
+    /// What is being typed in the chat, None when not typing
+    chat_input: Option<String>,
+    /// When the player whose turn it is runs out of time, if the server has a turn timer
+    turn_deadline: Option<Instant>,
+    game_over: bool,
+    quit: bool,
+}
+
+/// Runs the full-screen client on a stream that has already gone through the name handshake
+pub fn run(stream: TcpStream, name: String, config: ClientConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let (tx, rx) = mpsc::channel::<io::Result<ServerPacket>>();
+    let mut reader = stream.try_clone()?;
+    thread::spawn(move || loop {
+        let packet = read_packet::<ServerPacket>(&mut reader);
+        let failed = packet.is_err();
+        if tx.send(packet).is_err() || failed {
+            break;
+        }
+    });
+
+    let mut terminal = ratatui::init();
+    let mut app = App {
+        name, config, snapshot: None, rule_set: None, selected: 0, popup: None,
+        log: vec![], chat: vec![], chat_input: None, turn_deadline: None, game_over: false, quit: false,
+    };
+    let result = app.run(&mut terminal, stream, rx);
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn run(&mut self, terminal: &mut DefaultTerminal, mut stream: TcpStream, rx: mpsc::Receiver<io::Result<ServerPacket>>) -> Result<(), Box<dyn std::error::Error>> {
+        self.push_log(Line::from("Waiting for the game to start...".magenta()));
+        while !self.quit {
+            terminal.draw(|frame| {
+                self.draw(frame);
+                if !Theme::current().use_color {
+                    strip_colors(frame);
+                }
+            })?;
+            if event::poll(POLL_INTERVAL)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press {
+                        self.handle_key(key.code, &mut stream);
+                    }
+                }
+            }
+            while let Ok(packet) = rx.try_recv() {
+                match packet {
+                    Ok(packet) => self.handle_packet(packet, &mut stream),
+                    Err(e) if !self.game_over => {
+                        self.push_log(Line::from(format!("Lost connection to the server: {}", e).red()));
+                        self.game_over = true;
+                    }
+                    Err(_) => {},
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn push_log(&mut self, line: Line<'static>) {
+        self.log.push(line);
+        if self.log.len() > MAX_LOG_LINES {
+            self.log.remove(0);
+        }
+    }
+
+    fn push_chat(&mut self, line: Line<'static>) {
+        self.chat.push(line);
+        if self.chat.len() > MAX_LOG_LINES {
+            self.chat.remove(0);
+        }
+    }
+
+    fn is_my_turn(&self) -> bool {
+        !self.game_over && self.snapshot.as_ref().is_some_and(GameSnapshot::is_my_turn)
+    }
+
+    fn is_playable(&self, card: &Card) -> bool {
+        match (&self.snapshot, &self.rule_set) {
+            (Some(snapshot), Some(rule_set)) if self.is_my_turn() => rule_set.verify_card(&snapshot.table, &snapshot.hand, card).is_ok(),
+            _ => false,
+        }
+    }
+
+    fn selected_card(&self) -> Option<Card> {
+        self.snapshot.as_ref().and_then(|snapshot| snapshot.hand.iter().nth(self.selected).cloned())
+    }
+
+    fn handle_packet(&mut self, packet: ServerPacket, stream: &mut TcpStream) {
+        match packet {
+            ServerPacket::SendGameState { mut snapshot } => {
+                snapshot.hand.sort(self.config.hand_sort);
+                self.selected = self.selected.min(snapshot.hand.len().saturating_sub(1));
+                // Which deck is played with only matters to the server, the client only checks cards
+                self.rule_set = Some(snapshot.rules.rule_set(snapshot.rules.default_deck_definition()));
+                // A new turn makes any half finished move meaningless
+                self.popup = None;
+                // Only players still in a game get the table, so this may be the start of a rematch
+                self.game_over = false;
+                self.snapshot = Some(snapshot);
+                if self.is_my_turn() && self.config.auto_pick {
+                    let hand = self.snapshot.as_ref().unwrap().hand.clone();
+                    if !hand.iter().any(|card| self.is_playable(card)) {
+                        self.push_log(Line::from("Nothing to play, picking up.".magenta()));
+                        send_packet(stream, ClientPacket::SendMovePick);
+                    }
+                }
+            }
+            ServerPacket::SendMoveAcknowledgement { error: Some(error) } => self.push_log(Line::from(error.to_string().red())),
+            ServerPacket::SendMsg { msg: Some(msg) } => self.push_log(Line::from(msg)),
+            ServerPacket::SendChat { from, msg } => self.push_chat(Line::from(vec![format!("{}: ", from).bold(), Span::raw(msg)])),
+            ServerPacket::YouWon => {
+                self.push_log(Line::from("You Won!!".yellow().bold()));
+                self.game_over = true;
+            }
+            ServerPacket::YouLost => {
+                self.push_log(Line::from("You Lost.".red().bold()));
+                self.game_over = true;
+            }
+            ServerPacket::Ping { id } => send_packet(stream, ClientPacket::Pong { id }),
+            ServerPacket::TurnTimer { seconds } => self.turn_deadline = Some(Instant::now() + Duration::from_secs(seconds)),
+            ServerPacket::GameEnded => {
+                self.game_over = true;
+                self.turn_deadline = None;
+                match self.config.spectator {
+                    true => self.push_log(Line::from("The game is over.".bold())),
+                    false => self.push_log(Line::from("The game is over. Press r to vote for a rematch.".bold())),
+                }
+            }
+            _ => {},
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyCode, stream: &mut TcpStream) {
+        // Chat can be typed at any time, even with a popup open
+        if let Some(input) = self.chat_input.as_mut() {
+            match key {
+                KeyCode::Esc => self.chat_input = None,
+                KeyCode::Backspace => {input.pop();},
+                KeyCode::Char(c) if input.chars().count() < MAX_CHAT_LEN => input.push(c),
+                KeyCode::Enter => {
+                    let msg = self.chat_input.take().unwrap_or_default();
+                    // The host's commands are typed in the chat box too
+                    match parse_host_command(&msg) {
+                        Some(Ok(command)) => send_packet(stream, ClientPacket::HostCommand { command }),
+                        Some(Err(e)) => self.push_log(Line::from(e.red())),
+                        None if !msg.trim().is_empty() => send_packet(stream, ClientPacket::SendChat { msg }),
+                        None => {},
+                    }
+                }
+                _ => {},
+            }
+            return;
+        }
+        if key == KeyCode::Char('t') {
+            self.chat_input = Some(String::new());
+            return;
+        }
+        if let Some(popup) = self.popup.as_mut() {
+            let (selected, num_options) = match popup {
+                Popup::Color { selected, .. } => (selected, self.snapshot.as_ref().map_or(0, |snapshot| snapshot.wild_colors.len())),
+                Popup::Target { selected, .. } => (selected, self.snapshot.as_ref().map_or(0, |snapshot| snapshot.players.len())),
+            };
+            match key {
+                KeyCode::Esc | KeyCode::Char('q') => self.popup = None,
+                KeyCode::Up | KeyCode::Left => *selected = selected.saturating_sub(1),
+                KeyCode::Down | KeyCode::Right => *selected = (*selected + 1).min(num_options.saturating_sub(1)),
+                KeyCode::Enter => self.confirm_popup(stream),
+                _ => {},
+            }
+            return;
+        }
+
+        let hand_len = self.snapshot.as_ref().map_or(0, |snapshot| snapshot.hand.len());
+        match key {
+            KeyCode::Esc | KeyCode::Char('q') => self.quit = true,
+            KeyCode::Up | KeyCode::Left => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Right => self.selected = (self.selected + 1).min(hand_len.saturating_sub(1)),
+            KeyCode::Char(c @ '1'..='9') => {
+                let idx = c.to_digit(10).unwrap() as usize - 1;
+                if idx < hand_len {
+                    self.selected = idx;
+                }
+            }
+            KeyCode::Char('s') => {
+                self.config.hand_sort = self.config.hand_sort.next();
+                if let Some(snapshot) = self.snapshot.as_mut() {
+                    let selected_id = snapshot.hand.iter().nth(self.selected).map(|card| card.id);
+                    snapshot.hand.sort(self.config.hand_sort);
+                    // Keep the same card selected
+                    self.selected = snapshot.hand.iter().position(|card| Some(card.id) == selected_id).unwrap_or(0);
+                }
+                let sort = self.config.hand_sort;
+                self.push_log(Line::from(format!("Sorted by: {:?}", sort).magenta()));
+            }
+            KeyCode::Char('r') if self.game_over && !self.config.spectator => send_packet(stream, ClientPacket::VoteRematch),
+            KeyCode::Char('p') if self.is_my_turn() => send_packet(stream, ClientPacket::SendMovePick),
+            KeyCode::Enter if self.is_my_turn() => {
+                let Some(card) = self.selected_card() else {return};
+                let takes_target = self.rule_set.as_ref().is_some_and(|rule_set| rule_set.takes_target(&card));
+                if card.kind.is_wild() {
+                    self.popup = Some(Popup::Color { card_id: card.id, selected: 0 });
+                }
+                else if takes_target {
+                    self.popup = Some(Popup::Target { card_id: card.id, selected: 0 });
+                }
+                else {
+                    send_packet(stream, ClientPacket::SendMoveCard { card_id: card.id, color_choice: None, target_player: None });
+                }
+            }
+            KeyCode::Char('p') | KeyCode::Enter => self.push_log(Line::from("It is not your turn.".red())),
+            _ => {},
+        }
+    }
+
+    fn confirm_popup(&mut self, stream: &mut TcpStream) {
+        let (Some(popup), Some(snapshot)) = (self.popup.take(), self.snapshot.as_ref()) else {return};
+        let packet = match popup {
+            Popup::Color { card_id, selected } => {
+                let Some(&color) = snapshot.wild_colors.get(selected) else {return};
+                ClientPacket::SendMoveCard { card_id, color_choice: Some(color), target_player: None }
+            }
+            // Players are numbered from 1 on the wire, as in the line client
+            Popup::Target { card_id, selected } => ClientPacket::SendMoveCard { card_id, color_choice: None, target_player: Some(selected + 1) },
+        };
+        send_packet(stream, packet);
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main_area, help_area] = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [left_area, right_area] = Layout::horizontal([Constraint::Percentage(35), Constraint::Percentage(65)]).areas(main_area);
+        let [players_area, table_area] = Layout::vertical([Constraint::Min(0), Constraint::Length(7)]).areas(left_area);
+        let [hand_area, bottom_area] = Layout::vertical([Constraint::Min(0), Constraint::Length(10)]).areas(right_area);
+        let [log_area, chat_area] = Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(bottom_area);
+
+        self.draw_players(frame, players_area);
+        self.draw_table(frame, table_area);
+        self.draw_hand(frame, hand_area);
+        frame.render_widget(last_lines(&self.log, log_area.height.saturating_sub(2) as usize).block(Block::bordered().title(" Log ")), log_area);
+        self.draw_chat(frame, chat_area);
+
+        let help = match (self.game_over, &self.popup, &self.chat_input) {
+            (_, _, Some(_)) => "Enter send · Esc cancel",
+            (true, _, None) if self.config.spectator => "Game over · t chat · q quit",
+            (true, _, None) => "Game over · r rematch · t chat · q quit",
+            (false, Some(_), None) => "↑/↓ choose · Enter confirm · Esc cancel · t chat",
+            (false, None, None) if self.config.spectator => "t chat · q quit",
+            (false, None, None) => "←/→ or 1-9 select · Enter play · p pick up · s sort · t chat · q quit",
+        };
+        frame.render_widget(Paragraph::new(help.dark_gray()), help_area);
+
+        if let Some(popup) = &self.popup {
+            self.draw_popup(frame, popup);
+        }
+    }
+
+    fn draw_chat(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title(" Chat ");
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let input_height = if self.chat_input.is_some() {1} else {0};
+        let [messages_area, input_area] = Layout::vertical([Constraint::Min(0), Constraint::Length(input_height)]).areas(inner);
+        let messages = match self.chat.is_empty() {
+            true => Paragraph::new("No messages yet".dark_gray()),
+            false => last_lines(&self.chat, messages_area.height as usize),
+        };
+        frame.render_widget(messages, messages_area);
+        if let Some(input) = &self.chat_input {
+            frame.render_widget(Paragraph::new(format!("> {}_", input).bold()), input_area);
+        }
+    }
+
+    fn draw_players(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title(format!(" Players · you are {} ", self.name));
+        let Some(snapshot) = &self.snapshot else {
+            frame.render_widget(Paragraph::new("Waiting for the game to start...").block(block), area);
+            return;
+        };
+        let mut lines = snapshot.players.iter().enumerate().map(|(idx, player)| {
+            let mut spans = vec![
+                Span::raw(if idx == snapshot.turn {"▶ "} else {"  "}),
+                Span::raw(format!("{}. {} ({})", idx + 1, player.name, player.num_cards)),
+            ];
+            if Some(idx) == snapshot.you {
+                spans.push(" (you)".cyan());
+            }
+            if let Some(latency_ms) = player.latency_ms {
+                spans.push(format!(" {}ms", latency_ms).dark_gray());
+            }
+            if player.is_eliminated {
+                spans.push(" [out]".red());
+            }
+            else if !player.is_active {
+                spans.push(" [done]".green());
+            }
+            let line = Line::from(spans);
+            if idx == snapshot.turn {line.bold()} else {line}
+        }).collect::<Vec<Line>>();
+        lines.push(Line::from(""));
+        lines.push(Line::from(format!("Turns go {} the list", if snapshot.direction == Direction::Positive {"down"} else {"up"}).dark_gray()));
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_table(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title(" Table ");
+        let Some(snapshot) = &self.snapshot else {
+            frame.render_widget(block, area);
+            return;
+        };
+        let table = &snapshot.table;
+        let mut lines = vec![Line::from(vec![Span::raw("Topmost card: "), card_span(&table.top_card)])];
+        if snapshot.rules == RulesKind::Flip {
+            lines.push(Line::from(format!("Side in play: {}", if table.dark_side {"Dark"} else {"Light"})));
+        }
+        if table.card_debt > 0 {
+            lines.push(Line::from(format!("Cards to pick up: {}", table.card_debt).red()));
+        }
+        if let Some(color) = table.draw_until_color {
+            lines.push(Line::from(format!("Draw until you get a {} card", color).red()));
+        }
+        if let Some(deadline) = self.turn_deadline {
+            let seconds_left = deadline.saturating_duration_since(Instant::now()).as_secs();
+            let line = format!("Time left for this turn: {}s", seconds_left);
+            lines.push(Line::from(if seconds_left <= 10 {line.red()} else {line.into()}));
+        }
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_hand(&self, frame: &mut Frame, area: Rect) {
+        let title = match (self.config.spectator, self.is_my_turn()) {
+            (true, _) => " Watching the game · no hand ",
+            (false, true) => " Your hand · it is your turn! ",
+            (false, false) => " Your hand ",
+        };
+        let block = Block::bordered().title(title);
+        let Some(snapshot) = &self.snapshot else {
+            frame.render_widget(block, area);
+            return;
+        };
+        let items = snapshot.hand.iter().enumerate().map(|(idx, card)| {
+            let playable = self.is_playable(card);
+            let line = Line::from(vec![
+                Span::raw(format!("[{}] {} ", idx + 1, if playable {'*'} else {' '})),
+                card_span(card),
+            ]);
+            ListItem::new(if playable {line.bold()} else {line})
+        }).collect::<Vec<ListItem>>();
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+
+    fn draw_popup(&self, frame: &mut Frame, popup: &Popup) {
+        let Some(snapshot) = &self.snapshot else {return};
+        let (title, items, selected) = match popup {
+            Popup::Color { selected, .. } => {
+                let items = snapshot.wild_colors.iter().map(|&color| ListItem::new(Span::styled(color.to_string(), Style::new().fg(tui_color(Some(color))))));
+                (" Choose a color ", items.collect::<Vec<ListItem>>(), *selected)
+            }
+            Popup::Target { selected, .. } => {
+                let items = snapshot.players.iter().enumerate().map(|(idx, player)| ListItem::new(format!("{}. {}", idx + 1, player.name)));
+                (" Choose a player ", items.collect::<Vec<ListItem>>(), *selected)
+            }
+        };
+        let area = centered(frame.area(), 30, items.len() as u16 + 2);
+        let list = List::new(items)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        let mut state = ListState::default().with_selected(Some(selected));
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+}
+
+/// The latest `visible` lines
+fn last_lines(lines: &[Line<'static>], visible: usize) -> Paragraph<'static> {
+    let start = lines.len().saturating_sub(visible);
+    Paragraph::new(lines[start..].to_vec()).wrap(Wrap { trim: false })
+}
+
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let [area] = Layout::horizontal([Constraint::Length(width)]).flex(Flex::Center).areas(area);
+    let [area] = Layout::vertical([Constraint::Length(height)]).flex(Flex::Center).areas(area);
+    area
+}
+
+fn card_span(card: &Card) -> Span<'static> {
+    // Display shades uncolored cards with terminal escapes, which have no place in a widget
+    let text = match card.color {
+        Some(color) => match Theme::current().marker(color) {
+            Some(marker) => format!("{} ({})", card, marker),
+            None => card.to_string(),
+        },
+        None => card.kind.to_string(),
+    };
+    Span::styled(text, Style::new().fg(tui_color(card.color)))
+}
+
+/// Same colors as the line client
+fn tui_color(color: Option<Color>) -> TuiColor {
+    match color.map(|color| color.terminal_color()) {
+        Some(colored::Color::Red) => TuiColor::Red,
+        Some(colored::Color::Green) => TuiColor::Green,
+        Some(colored::Color::Blue) => TuiColor::Blue,
+        Some(colored::Color::Yellow) => TuiColor::Yellow,
+        Some(colored::Color::TrueColor { r, g, b }) => TuiColor::Rgb(r, g, b),
+        // Wilds in the hand have no color yet
+        _ => TuiColor::Cyan,
+    }
+}
+
+/// With NO_COLOR set only bold and reversed text are left to tell things apart
+fn strip_colors(frame: &mut Frame) {
+    for cell in frame.buffer_mut().content.iter_mut() {
+        cell.set_fg(TuiColor::Reset).set_bg(TuiColor::Reset);
+    }
+}